@@ -0,0 +1,231 @@
+use kepler_core::{
+    center_of_mass::calculate_center_of_mass, impulse::calculate_total_impulse, types::System,
+};
+use maths_rs::Vec2d;
+use serde::Serialize;
+
+use crate::mover::Mover;
+
+/// Chaos indicators estimated from the divergence of the shadow system
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ChaosIndicators {
+    /// Finite-time maximal Lyapunov exponent in 1/s
+    pub lyapunov_exponent: f64,
+    /// MEGNO ⟨Y⟩, tends to 2 for quasi-periodic and grows as λt/2 for chaotic motion
+    pub megno: f64,
+}
+
+/// Integrates a slightly perturbed copy of the system alongside the run and
+/// estimates the maximal Lyapunov exponent and MEGNO from how fast the two separate.
+///
+/// The separation is measured in phase space, with the velocities scaled by the
+/// crossing time of the system, and renormalised to its initial length after every
+/// step, so it always stays in the linear regime.
+pub struct ChaosTracker {
+    shadow: System,
+    /// Converts velocity differences into lengths
+    crossing_time: f64,
+    initial_distance: f64,
+    /// Σ ln(dₖ/d₀)
+    log_growth: f64,
+    /// Σ ln(dₖ/d₀) tₖ
+    weighted_log_growth: f64,
+    /// ∫ Y dt
+    megno_integral: f64,
+    megno: f64,
+    time: f64,
+}
+
+fn length(vector: Vec2d) -> f64 {
+    (vector.x * vector.x + vector.y * vector.y).sqrt()
+}
+
+impl ChaosTracker {
+    /// Creates the shadow system by stretching the positions and velocities of all
+    /// bodies about the center of mass by `1 + perturbation`.
+    ///
+    /// Returns `None` if that does not perturb anything, e.g. for a single body at rest.
+    pub fn new(system: &System, perturbation: f64) -> Option<Self> {
+        let mass: f64 = system.bodies.iter().map(|body| body.mass).sum();
+        let center_of_mass = calculate_center_of_mass(system);
+        let impulse = calculate_total_impulse(system);
+        let center_of_mass_velocity = Vec2d::new(impulse.x / mass, impulse.y / mass);
+
+        let extent = system
+            .bodies
+            .iter()
+            .map(|body| length(body.position - center_of_mass))
+            .fold(0.0, f64::max);
+        let max_speed = system
+            .bodies
+            .iter()
+            .map(|body| length(body.velocity - center_of_mass_velocity))
+            .fold(0.0, f64::max);
+        let crossing_time = if max_speed > 0.0 {
+            extent / max_speed
+        } else {
+            0.0
+        };
+
+        let mut shadow = system.clone();
+        for body in shadow.bodies.iter_mut() {
+            let position = body.position - center_of_mass;
+            let velocity = body.velocity - center_of_mass_velocity;
+            body.position = body.position + position * perturbation;
+            body.velocity = body.velocity + velocity * perturbation;
+        }
+
+        let mut tracker = Self {
+            shadow,
+            crossing_time,
+            initial_distance: 0.0,
+            log_growth: 0.0,
+            weighted_log_growth: 0.0,
+            megno_integral: 0.0,
+            megno: 0.0,
+            time: 0.0,
+        };
+        tracker.initial_distance = tracker.distance(system);
+        (tracker.initial_distance.is_finite() && tracker.initial_distance > 0.0).then_some(tracker)
+    }
+
+    /// The distance of the shadow system from `system` in phase space
+    fn distance(&self, system: &System) -> f64 {
+        system
+            .bodies
+            .iter()
+            .zip(self.shadow.bodies.iter())
+            .map(|(body, shadow)| {
+                let position = length(shadow.position - body.position);
+                let velocity = length(shadow.velocity - body.velocity) * self.crossing_time;
+                position * position + velocity * velocity
+            })
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Moves the shadow system by the same timestep as `system` was just moved,
+    /// updates the indicators and renormalises the separation
+    pub fn track_step(&mut self, mover: &Mover, system: &System, timestep: f64) {
+        let shadow = std::mem::replace(&mut self.shadow, System { bodies: vec![] });
+        self.shadow = mover.timestep(shadow, timestep);
+        self.time += timestep.abs();
+
+        let distance = self.distance(system);
+        if !distance.is_finite() || distance == 0.0 {
+            return;
+        }
+        let log_growth = (distance / self.initial_distance).ln();
+        self.log_growth += log_growth;
+        self.weighted_log_growth += log_growth * self.time;
+        let megno = 2.0 * self.weighted_log_growth / self.time;
+        self.megno_integral += megno * timestep.abs();
+        self.megno = self.megno_integral / self.time;
+
+        let scale = self.initial_distance / distance;
+        for (shadow, body) in self.shadow.bodies.iter_mut().zip(system.bodies.iter()) {
+            shadow.position = body.position + (shadow.position - body.position) * scale;
+            shadow.velocity = body.velocity + (shadow.velocity - body.velocity) * scale;
+        }
+    }
+
+    /// The indicators for the simulated time so far, zero before the first step
+    pub fn indicators(&self) -> ChaosIndicators {
+        ChaosIndicators {
+            lyapunov_exponent: if self.time > 0.0 {
+                self.log_growth / self.time
+            } else {
+                0.0
+            },
+            megno: self.megno,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ChaosTracker;
+    use crate::{
+        generate::{circular_speed, orbital_period, ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS},
+        gravity::GRAVITATIONAL_CONSTANT,
+        mover::Mover,
+    };
+    use kepler_core::types::{Body, System};
+    use maths_rs::Vec2d;
+
+    #[test]
+    pub fn finds_a_circular_orbit_regular() {
+        let mut system = System {
+            bodies: vec![
+                Body {
+                    name: "Sun".to_owned(),
+                    mass: SUN_MASS,
+                    position: Vec2d::new(0.0, 0.0),
+                    velocity: Vec2d::new(0.0, 0.0),
+                },
+                Body {
+                    name: "Earth".to_owned(),
+                    mass: EARTH_MASS,
+                    position: Vec2d::new(ASTRONOMICAL_UNIT, 0.0),
+                    velocity: Vec2d::new(0.0, circular_speed(SUN_MASS, ASTRONOMICAL_UNIT)),
+                },
+            ],
+        };
+        let period = orbital_period(SUN_MASS, ASTRONOMICAL_UNIT);
+        let timestep = period / 1000.0;
+        let mover = Mover::new(None);
+        let mut tracker = ChaosTracker::new(&system, 1e-8).unwrap();
+        for _ in 0..50_000 {
+            system = mover.timestep(system, timestep);
+            tracker.track_step(&mover, &system, timestep);
+        }
+
+        let indicators = tracker.indicators();
+        assert!((indicators.megno - 2.0).abs() < 0.3, "{indicators:?}");
+        // far slower than one e-fold per orbit
+        assert!(
+            indicators.lyapunov_exponent * period < 0.2,
+            "{indicators:?}"
+        );
+    }
+
+    #[test]
+    pub fn finds_the_pythagorean_three_body_problem_chaotic() {
+        // masses 3, 4 and 5 at rest at the corners of a 3-4-5 triangle, Burrau (1913)
+        let body = |name: &str, mass: f64, x: f64, y: f64| Body {
+            name: name.to_owned(),
+            mass: mass * SUN_MASS,
+            position: Vec2d::new(x, y) * ASTRONOMICAL_UNIT,
+            velocity: Vec2d::new(0.0, 0.0),
+        };
+        let mut system = System {
+            bodies: vec![
+                body("A", 3.0, 1.0, 3.0),
+                body("B", 4.0, -2.0, -1.0),
+                body("C", 5.0, 1.0, -1.0),
+            ],
+        };
+        // the unit of time for G = M☉ = AU = 1
+        let time_unit = (ASTRONOMICAL_UNIT.powi(3) / (GRAVITATIONAL_CONSTANT * SUN_MASS)).sqrt();
+        let timestep = 1e-4 * time_unit;
+        let mover = Mover::new(None);
+        let mut tracker = ChaosTracker::new(&system, 1e-8).unwrap();
+        let mut megno = vec![];
+        for step in 1..=200_000 {
+            system = mover.timestep(system, timestep);
+            tracker.track_step(&mover, &system, timestep);
+            if step % 100_000 == 0 {
+                megno.push(tracker.indicators().megno);
+            }
+        }
+
+        let indicators = tracker.indicators();
+        // the close encounters separate neighbouring trajectories by about one
+        // e-fold per unit of time, and MEGNO keeps growing instead of settling at 2
+        assert!(
+            indicators.lyapunov_exponent * time_unit > 0.5,
+            "{indicators:?}"
+        );
+        assert!(megno[1] > 5.0 && megno[1] > megno[0], "{megno:?}");
+    }
+}
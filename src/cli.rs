@@ -0,0 +1,35 @@
+//! The subcommands of the command line interface and what only they need
+
+/// Comparing the trajectories of two runs
+pub mod compare;
+/// Runs at halved timesteps
+pub mod convergence;
+/// Rewriting a config in another format
+pub mod convert;
+/// Writing generated systems
+pub mod generate;
+/// Periods and resonances of a run
+pub mod periodogram;
+/// Writing the config of a built-in system
+pub mod preset;
+/// The progress bar of the `run` subcommand
+pub mod progress;
+/// Integrating forward and back
+pub mod reversibility;
+/// Live view in the browser
+pub mod serve;
+/// Parameter sweeps
+pub mod sweep;
+/// Live view in the terminal for `run --tui`
+pub mod tui;
+/// Checking a config without running it
+pub mod validate;
+
+/// The `--set` option shared by all subcommands that read a config
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct OverrideArgs {
+    /// Replace a value of the config, e.g. `--set config.timestep=200` or
+    /// `--set system.bodies[Earth].mass=6e24`. Can be given multiple times.
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    pub overrides: Vec<String>,
+}
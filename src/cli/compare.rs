@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use clap::Args;
+use kepler_cli::{
+    compare::{compare_trajectories, Comparison},
+    error::ApplicationError,
+    plot::{plot_divergence, LogSeries},
+    trajectory::read_trajectories,
+};
+
+#[derive(Args, Debug)]
+pub struct CompareArgs {
+    /// Export directory or `.jsonl` stream of the first run
+    pub first: String,
+
+    /// Export directory or `.jsonl` stream of the second run
+    pub second: String,
+
+    /// `export_file_name_prefix` of the runs when comparing export directories
+    #[arg(long, default_value = "SIM")]
+    pub prefix: String,
+
+    /// Report when the position divergence of a body first exceeds this distance in m
+    #[arg(long)]
+    pub threshold: Option<f64>,
+
+    /// Write the divergence of every body at every compared step to this csv file
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Plot the position divergence over time into this svg file
+    #[arg(long)]
+    pub plot: Option<String>,
+}
+
+/// Compares the body trajectories of two runs and reports how far they diverge
+pub fn compare(args: CompareArgs) -> Result<(), ApplicationError> {
+    let first = read_trajectories(&args.first, &args.prefix)?;
+    let second = read_trajectories(&args.second, &args.prefix)?;
+    let comparison = compare_trajectories(&first, &second, args.threshold);
+    if comparison.bodies.is_empty() {
+        return Err(ApplicationError::TrajectoryError(
+            "the runs have no bodies in common".to_owned(),
+        ));
+    }
+    if comparison
+        .bodies
+        .iter()
+        .all(|body| body.divergence.is_empty())
+    {
+        return Err(ApplicationError::TrajectoryError(
+            "the runs exported no states at the same times".to_owned(),
+        ));
+    }
+
+    if let Some(output) = &args.output {
+        write_divergence_csv(&comparison, output)?;
+    }
+    if let Some(plot) = &args.plot {
+        let series: Vec<LogSeries> = comparison
+            .bodies
+            .iter()
+            .map(|body| LogSeries {
+                label: &body.name,
+                points: body
+                    .divergence
+                    .iter()
+                    .map(|divergence| (divergence.time, divergence.position))
+                    .collect(),
+            })
+            .collect();
+        match plot_divergence(&series, Path::new(plot), "Position divergence / m") {
+            Ok(_) => tracing::event!(tracing::Level::INFO, "Plotted divergence"),
+            Err(e) => tracing::event!(
+                tracing::Level::ERROR,
+                "Error while plotting divergence: {e}"
+            ),
+        }
+    }
+
+    print!("{comparison}");
+    Ok(())
+}
+
+fn write_divergence_csv(comparison: &Comparison, output: &str) -> Result<(), ApplicationError> {
+    let mut writer = csv::Writer::from_path(output)?;
+    writer.write_record(["Step", "Time", "Body", "position", "velocity"])?;
+    for body in comparison.bodies.iter() {
+        for divergence in body.divergence.iter() {
+            writer.serialize((
+                divergence.step,
+                divergence.time,
+                &body.name,
+                divergence.position,
+                divergence.velocity,
+            ))?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
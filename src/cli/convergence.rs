@@ -0,0 +1,87 @@
+use clap::Args;
+use kepler_cli::{
+    configsystem::{ConfigFormat, ConfigSystem},
+    configvalue::apply_overrides,
+    convergence::study_convergence,
+    error::ApplicationError,
+    plot::{plot_convergence, LogSeries},
+};
+
+use crate::cli::OverrideArgs;
+
+#[derive(Args, Debug)]
+pub struct ConvergenceArgs {
+    /// Path to the config file with the simulation details
+    #[arg(short, long)]
+    pub filename: String,
+
+    /// Format of the config file, taken from the file extension if not given
+    #[arg(long, value_enum)]
+    pub format: Option<ConfigFormat>,
+
+    /// Number of runs, every run halves the timestep of the previous one
+    #[arg(short, long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(2..=20))]
+    pub runs: u32,
+
+    /// Number of threads used for the force evaluation, overrides `threads` in the config
+    #[arg(short, long)]
+    pub threads: Option<usize>,
+
+    /// Print the results as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub overrides: OverrideArgs,
+}
+
+/// Runs a config at halved timesteps, prints the errors and plots them over the timestep
+pub fn convergence(args: ConvergenceArgs) -> Result<(), ApplicationError> {
+    let configsystem = apply_overrides(
+        ConfigSystem::parse_as(args.filename, args.format)?,
+        &args.overrides.overrides,
+    )?;
+    let mut config = configsystem.config;
+    if args.threads.is_some() {
+        config.threads = args.threads;
+    }
+    let study = study_convergence(
+        &config,
+        configsystem.system,
+        configsystem.test_particles,
+        args.runs,
+    )?;
+
+    let series = [
+        LogSeries {
+            label: "Max. relative energy error",
+            points: study
+                .runs
+                .iter()
+                .map(|run| (run.timestep, run.max_relative_energy_error))
+                .collect(),
+        },
+        LogSeries {
+            label: "Max. Δ position to previous run / extent",
+            points: study
+                .runs
+                .iter()
+                .filter_map(|run| Some((run.timestep, run.position_difference? / study.extent)))
+                .collect(),
+        },
+    ];
+    match plot_convergence(&series, &config) {
+        Ok(_) => tracing::event!(tracing::Level::INFO, "Plotted convergence"),
+        Err(e) => tracing::event!(
+            tracing::Level::ERROR,
+            "Error while plotting convergence: {e}"
+        ),
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&study)?);
+    } else {
+        print!("{study}");
+    }
+    Ok(())
+}
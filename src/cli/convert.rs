@@ -0,0 +1,58 @@
+use clap::Args;
+
+use kepler_cli::{
+    configsystem::{ConfigFormat, ConfigSystem},
+    configvalue::apply_overrides,
+    error::ApplicationError,
+};
+
+use crate::cli::OverrideArgs;
+
+#[derive(Args, Debug)]
+pub struct ConvertArgs {
+    /// Path to the config file to convert
+    #[arg(short, long)]
+    pub filename: String,
+
+    /// Format of the input, taken from the file extension if not given
+    #[arg(long, value_enum)]
+    pub from: Option<ConfigFormat>,
+
+    /// Path of the converted config, written to stdout if not given
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Format of the output, taken from the extension of `--output` if not given
+    #[arg(long, value_enum)]
+    pub to: Option<ConfigFormat>,
+
+    #[command(flatten)]
+    pub overrides: OverrideArgs,
+}
+
+/// Rewrites a config in another format
+pub fn convert(args: ConvertArgs) -> Result<(), ApplicationError> {
+    let configsystem = apply_overrides(
+        ConfigSystem::parse_as(args.filename, args.from)?,
+        &args.overrides.overrides,
+    )?;
+    let format = match (&args.output, args.to) {
+        (_, Some(format)) => format,
+        (Some(output), None) => ConfigFormat::from_path(output).ok_or_else(|| {
+            ApplicationError::ConfigPathError(format!(
+                "cannot tell the format of `{output}` from its extension, use --to"
+            ))
+        })?,
+        (None, None) => {
+            return Err(ApplicationError::ConfigPathError(
+                "--to is needed when writing to stdout".to_owned(),
+            ))
+        }
+    };
+    let contents = configsystem.to_string_as(format)?;
+    match args.output {
+        Some(output) => std::fs::write(output, contents)?,
+        None => print!("{contents}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use clap::{Args, Subcommand};
+use kepler_cli::{
+    configsystem::ConfigFormat,
+    error::ApplicationError,
+    generate::{
+        generate_binary, generate_figure_eight, generate_planets, generate_plummer, generate_ring,
+        ASTRONOMICAL_UNIT, EARTH_MASS, JUPITER_MASS, SUN_MASS,
+    },
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Args, Debug)]
+pub struct GenerateArgs {
+    #[command(subcommand)]
+    pub generator: Generator,
+
+    /// Path of the config file to write, the format is taken from the extension
+    #[arg(short, long, global = true, default_value = "generated.toml")]
+    pub output: String,
+
+    /// Seed for the random number generator, the same seed gives the same system
+    #[arg(short, long, global = true, default_value_t = 0)]
+    pub seed: u64,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Generator {
+    /// A central star with planets on randomized near-circular orbits
+    Planets {
+        /// Number of planets
+        #[arg(long, default_value_t = 8)]
+        count: usize,
+        /// Mass of the central star in kg
+        #[arg(long, default_value_t = SUN_MASS)]
+        star_mass: f64,
+        /// Smallest planet mass in kg
+        #[arg(long, default_value_t = 0.055 * EARTH_MASS)]
+        min_mass: f64,
+        /// Largest planet mass in kg
+        #[arg(long, default_value_t = JUPITER_MASS)]
+        max_mass: f64,
+        /// Smallest orbit radius in m
+        #[arg(long, default_value_t = 0.4 * ASTRONOMICAL_UNIT)]
+        min_distance: f64,
+        /// Largest orbit radius in m
+        #[arg(long, default_value_t = 30.0 * ASTRONOMICAL_UNIT)]
+        max_distance: f64,
+        /// Largest eccentricity of the orbits
+        #[arg(long, default_value_t = 0.05)]
+        max_eccentricity: f64,
+    },
+    /// A star cluster following the Plummer model, projected onto the plane.
+    /// The velocities are scaled to virial equilibrium, but the projected
+    /// sphere is no planar equilibrium model and relaxes at first.
+    Plummer {
+        /// Number of stars
+        #[arg(long, default_value_t = 100)]
+        count: usize,
+        /// Total mass of the cluster in kg
+        #[arg(long, default_value_t = 100.0 * SUN_MASS)]
+        total_mass: f64,
+        /// Plummer radius in m
+        #[arg(long, default_value_t = 1000.0 * ASTRONOMICAL_UNIT)]
+        scale_radius: f64,
+    },
+    /// A central star surrounded by a ring of test particles on circular orbits
+    Ring {
+        /// Number of test particles
+        #[arg(long, default_value_t = 1000)]
+        count: usize,
+        /// Mass of the central star in kg
+        #[arg(long, default_value_t = SUN_MASS)]
+        star_mass: f64,
+        /// Inner radius of the ring in m
+        #[arg(long, default_value_t = 2.0 * ASTRONOMICAL_UNIT)]
+        inner_radius: f64,
+        /// Outer radius of the ring in m
+        #[arg(long, default_value_t = 3.5 * ASTRONOMICAL_UNIT)]
+        outer_radius: f64,
+    },
+    /// Two stars on a circular orbit with planets orbiting both of them
+    Binary {
+        /// Mass of the primary star in kg
+        #[arg(long, default_value_t = SUN_MASS)]
+        primary_mass: f64,
+        /// Mass of the secondary star in kg
+        #[arg(long, default_value_t = 0.5 * SUN_MASS)]
+        secondary_mass: f64,
+        /// Distance between the stars in m
+        #[arg(long, default_value_t = 0.2 * ASTRONOMICAL_UNIT)]
+        separation: f64,
+        /// Number of circumbinary planets
+        #[arg(long, default_value_t = 2)]
+        planets: usize,
+    },
+    /// The figure-eight choreography of three equal masses
+    FigureEight {
+        /// Mass of each body in kg
+        #[arg(long, default_value_t = SUN_MASS)]
+        mass: f64,
+        /// Length scale in m, the figure eight is about 2 scales wide
+        #[arg(long, default_value_t = ASTRONOMICAL_UNIT)]
+        scale: f64,
+        /// Random relative perturbation applied to the initial velocities
+        #[arg(long, default_value_t = 0.0)]
+        perturbation: f64,
+    },
+}
+
+/// Fails unless `value` is a positive number
+fn check_positive(name: &str, value: f64) -> Result<(), ApplicationError> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(ApplicationError::InvalidArgumentError(format!(
+            "--{name} must be positive, but is {value}"
+        )))
+    }
+}
+
+/// Fails unless `min` and `max` are positive and `min` is not larger than `max`
+fn check_range(name: &str, min: f64, max: f64) -> Result<(), ApplicationError> {
+    check_positive(&format!("min-{name}"), min)?;
+    check_positive(&format!("max-{name}"), max)?;
+    if min <= max {
+        Ok(())
+    } else {
+        Err(ApplicationError::InvalidArgumentError(format!(
+            "--min-{name} {min} is larger than --max-{name} {max}"
+        )))
+    }
+}
+
+impl Generator {
+    /// Checks the arguments before anything is drawn from them, empty or
+    /// inverted ranges would make the generators panic
+    pub fn validate(&self) -> Result<(), ApplicationError> {
+        match *self {
+            Generator::Planets {
+                star_mass,
+                min_mass,
+                max_mass,
+                min_distance,
+                max_distance,
+                max_eccentricity,
+                ..
+            } => {
+                check_positive("star-mass", star_mass)?;
+                check_range("mass", min_mass, max_mass)?;
+                check_range("distance", min_distance, max_distance)?;
+                if !(0.0..1.0).contains(&max_eccentricity) {
+                    return Err(ApplicationError::InvalidArgumentError(format!(
+                        "--max-eccentricity must be at least 0 and below 1, but is {max_eccentricity}"
+                    )));
+                }
+            }
+            Generator::Plummer {
+                total_mass,
+                scale_radius,
+                ..
+            } => {
+                check_positive("total-mass", total_mass)?;
+                check_positive("scale-radius", scale_radius)?;
+            }
+            Generator::Ring {
+                star_mass,
+                inner_radius,
+                outer_radius,
+                ..
+            } => {
+                check_positive("star-mass", star_mass)?;
+                check_positive("inner-radius", inner_radius)?;
+                check_positive("outer-radius", outer_radius)?;
+                if inner_radius > outer_radius {
+                    return Err(ApplicationError::InvalidArgumentError(format!(
+                        "--inner-radius {inner_radius} is larger than --outer-radius {outer_radius}"
+                    )));
+                }
+            }
+            Generator::Binary {
+                primary_mass,
+                secondary_mass,
+                separation,
+                ..
+            } => {
+                check_positive("primary-mass", primary_mass)?;
+                check_positive("secondary-mass", secondary_mass)?;
+                check_positive("separation", separation)?;
+            }
+            Generator::FigureEight {
+                mass,
+                scale,
+                perturbation,
+            } => {
+                check_positive("mass", mass)?;
+                check_positive("scale", scale)?;
+                if !(0.0..1.0).contains(&perturbation) {
+                    return Err(ApplicationError::InvalidArgumentError(format!(
+                        "--perturbation must be at least 0 and below 1, but is {perturbation}"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn generate(args: GenerateArgs) -> Result<(), ApplicationError> {
+    args.generator.validate()?;
+    let mut rng = ChaCha8Rng::seed_from_u64(args.seed);
+    let configsystem = match args.generator {
+        Generator::Planets {
+            count,
+            star_mass,
+            min_mass,
+            max_mass,
+            min_distance,
+            max_distance,
+            max_eccentricity,
+        } => generate_planets(
+            &mut rng,
+            count,
+            star_mass,
+            (min_mass, max_mass),
+            (min_distance, max_distance),
+            max_eccentricity,
+        ),
+        Generator::Plummer {
+            count,
+            total_mass,
+            scale_radius,
+        } => generate_plummer(&mut rng, count, total_mass, scale_radius),
+        Generator::Ring {
+            count,
+            star_mass,
+            inner_radius,
+            outer_radius,
+        } => generate_ring(&mut rng, count, star_mass, inner_radius, outer_radius),
+        Generator::Binary {
+            primary_mass,
+            secondary_mass,
+            separation,
+            planets,
+        } => generate_binary(&mut rng, primary_mass, secondary_mass, separation, planets),
+        Generator::FigureEight {
+            mass,
+            scale,
+            perturbation,
+        } => generate_figure_eight(&mut rng, mass, scale, perturbation),
+    };
+
+    let contents = configsystem.to_string_as(ConfigFormat::detect(None, &args.output))?;
+    std::fs::write(&args.output, contents)?;
+    tracing::event!(
+        tracing::Level::INFO,
+        "Wrote {} bodies and {} test particles to {}",
+        configsystem.system.bodies.len(),
+        configsystem.test_particles.len(),
+        args.output
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use kepler_cli::generate::{ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS};
+
+    use super::Generator;
+
+    #[test]
+    pub fn rejects_empty_and_inverted_ranges() {
+        let planets = |min_distance, max_distance| Generator::Planets {
+            count: 5,
+            star_mass: SUN_MASS,
+            min_mass: EARTH_MASS,
+            max_mass: 100.0 * EARTH_MASS,
+            min_distance,
+            max_distance,
+            max_eccentricity: 0.05,
+        };
+        assert!(planets(ASTRONOMICAL_UNIT, ASTRONOMICAL_UNIT)
+            .validate()
+            .is_ok());
+        let error = planets(10.0 * ASTRONOMICAL_UNIT, ASTRONOMICAL_UNIT)
+            .validate()
+            .unwrap_err();
+        assert!(error.to_string().starts_with("--min-distance"), "{error}");
+        assert!(planets(0.0, ASTRONOMICAL_UNIT).validate().is_err());
+        assert!(planets(f64::NAN, ASTRONOMICAL_UNIT).validate().is_err());
+    }
+}
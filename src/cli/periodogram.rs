@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use clap::Args;
+use kepler_cli::{
+    error::ApplicationError,
+    periodogram::{analyse_periods, PeriodAnalysis},
+    plot::{plot_periodogram, LogSeries},
+    trajectory::read_trajectories,
+};
+
+#[derive(Args, Debug)]
+pub struct PeriodogramArgs {
+    /// Export directory with body histories or `.jsonl` stream of the run
+    pub input: String,
+
+    /// `export_file_name_prefix` of the run when reading an export directory
+    #[arg(long, default_value = "SIM")]
+    pub prefix: String,
+
+    /// Body the orbits refer to, the most massive body if not given
+    #[arg(long)]
+    pub primary: Option<String>,
+
+    /// Largest relative deviation of a period ratio from p/q that counts as resonance
+    #[arg(long, default_value_t = 0.01)]
+    pub tolerance: f64,
+
+    /// Largest p and q of the period ratios p/q checked for resonances
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u32).range(1..=20))]
+    pub max_term: u32,
+
+    /// Write the periodograms of all bodies and series to this csv file
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Plot the periodograms of the x coordinates into this svg file
+    #[arg(long)]
+    pub plot: Option<String>,
+
+    /// Print the periods and resonances as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Reads the trajectories of a run, prints the dominant periods of every body and
+/// the resonances between them, and writes and plots the periodograms
+pub fn periodogram(args: PeriodogramArgs) -> Result<(), ApplicationError> {
+    let trajectories = read_trajectories(&args.input, &args.prefix)?;
+    let analysis = analyse_periods(
+        &trajectories,
+        args.primary.as_deref(),
+        args.tolerance,
+        args.max_term,
+    )?;
+
+    if let Some(output) = &args.output {
+        write_periodogram_csv(&analysis, output)?;
+    }
+    if let Some(plot) = &args.plot {
+        let series: Vec<LogSeries> = analysis
+            .series
+            .iter()
+            .filter(|series| series.series == "x" && series.body != analysis.primary)
+            .map(|series| LogSeries {
+                label: &series.body,
+                points: series
+                    .periodogram
+                    .frequencies
+                    .iter()
+                    .zip(series.periodogram.power.iter())
+                    .map(|(frequency, power)| (1.0 / frequency, *power))
+                    .collect(),
+            })
+            .collect();
+        match plot_periodogram(&series, Path::new(plot)) {
+            Ok(_) => tracing::event!(tracing::Level::INFO, "Plotted periodogram"),
+            Err(e) => tracing::event!(
+                tracing::Level::ERROR,
+                "Error while plotting periodogram: {e}"
+            ),
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&analysis)?);
+    } else {
+        print!("{analysis}");
+    }
+    Ok(())
+}
+
+fn write_periodogram_csv(analysis: &PeriodAnalysis, output: &str) -> Result<(), ApplicationError> {
+    let mut writer = csv::Writer::from_path(output)?;
+    writer.write_record(["Body", "Series", "Frequency", "Period", "Power"])?;
+    for series in analysis.series.iter() {
+        let periodogram = &series.periodogram;
+        for (frequency, power) in periodogram.frequencies.iter().zip(periodogram.power.iter()) {
+            writer.serialize((
+                &series.body,
+                series.series,
+                frequency,
+                1.0 / frequency,
+                power,
+            ))?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use std::path::Path;
+
+use clap::Args;
+use kepler_cli::{configsystem::ConfigFormat, error::ApplicationError, preset::Preset};
+
+#[derive(Args, Debug)]
+pub struct InitArgs {
+    /// The system to write
+    #[arg(short, long, value_enum)]
+    pub preset: Preset,
+
+    /// Path of the config file to write, the format is taken from the extension.
+    /// Defaults to the name of the preset with a `.toml` extension.
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Replace the output file if it already exists
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// Writes the config of a preset so it can be edited
+pub fn init(args: InitArgs) -> Result<(), ApplicationError> {
+    let output = args
+        .output
+        .unwrap_or_else(|| format!("{}.toml", args.preset.name()));
+    if !args.force && Path::new(&output).exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{output} already exists, use --force to replace it"),
+        )
+        .into());
+    }
+    let configsystem = args.preset.configsystem();
+    std::fs::write(
+        &output,
+        configsystem.to_string_as(ConfigFormat::detect(None, &output))?,
+    )?;
+    tracing::event!(
+        tracing::Level::INFO,
+        "Wrote the preset {} to {output}",
+        args.preset.name()
+    );
+    Ok(())
+}
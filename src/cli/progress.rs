@@ -0,0 +1,129 @@
+use std::{
+    io::IsTerminal,
+    time::{Duration, Instant},
+};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use kepler_core::types::System;
+use maths_rs::num::Cast;
+
+use kepler_cli::{
+    configsystem::{Config, TestParticle},
+    simulation::{format_signed_time, format_time, Monitor},
+};
+
+/// How often the simulated time of the progress bar is updated
+const MESSAGE_INTERVAL: Duration = Duration::from_millis(100);
+/// How often the progress is logged when stderr is not a terminal
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+/// Target of the progress log lines, the default log filter shows them
+pub const LOG_TARGET: &str = "kepler_cli::progress";
+
+/// Reports the progress of a run on stderr
+///
+/// On a terminal this is a progress bar, otherwise a log line every few seconds.
+pub enum Progress {
+    Bar {
+        bar: ProgressBar,
+        last_message: Instant,
+    },
+    Log {
+        start: Instant,
+        last_log: Instant,
+    },
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        if std::io::stderr().is_terminal() {
+            let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{bar:40.cyan/blue} {percent:>3}% {msg} · {per_sec} · ETA {eta}",
+                )
+                .expect("that the progress bar template is valid")
+                .progress_chars("█▉▊▋▌▍▎▏ "),
+            );
+            Progress::Bar {
+                bar,
+                last_message: now,
+            }
+        } else {
+            Progress::Log {
+                start: now,
+                last_log: now,
+            }
+        }
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Monitor for Progress {
+    fn start(&mut self, config: &Config, _system: &System, _test_particles: &[TestParticle]) {
+        match self {
+            Progress::Bar { bar, .. } => {
+                bar.set_length(config.steps.max(0).as_u64());
+                bar.reset();
+            }
+            Progress::Log { start, last_log } => {
+                *start = Instant::now();
+                *last_log = *start;
+            }
+        }
+    }
+
+    fn step(
+        &mut self,
+        config: &Config,
+        _system: &System,
+        _test_particles: &[TestParticle],
+        step: i64,
+        time: f64,
+    ) -> bool {
+        match self {
+            Progress::Bar { bar, last_message } => {
+                bar.set_position(step.as_u64());
+                if last_message.elapsed() >= MESSAGE_INTERVAL || step == config.steps {
+                    *last_message = Instant::now();
+                    bar.set_message(format!("{} simulated", format_signed_time(time)));
+                }
+                if step == config.steps {
+                    bar.finish();
+                }
+            }
+            Progress::Log { start, last_log } => {
+                if last_log.elapsed() >= LOG_INTERVAL || step == config.steps {
+                    *last_log = Instant::now();
+                    let elapsed = start.elapsed().as_secs_f64();
+                    let steps_per_second = step.as_f64() / elapsed;
+                    let remaining = (config.steps - step).as_f64() / steps_per_second;
+                    tracing::event!(
+                        target: LOG_TARGET,
+                        tracing::Level::INFO,
+                        "Progress: {:.2}%, time: {}, {:.0} steps/s, ETA {}",
+                        step.as_f64() / config.steps.as_f64() * 100.0,
+                        format_signed_time(time),
+                        steps_per_second,
+                        format_time(remaining.as_u64()),
+                    );
+                }
+            }
+        }
+        true
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        // a stopped run leaves the bar where it was
+        if let Progress::Bar { bar, .. } = self {
+            bar.abandon();
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use clap::Args;
+use kepler_cli::{
+    configsystem::{ConfigFormat, ConfigSystem},
+    configvalue::apply_overrides,
+    error::ApplicationError,
+    reversibility::check_reversibility,
+};
+
+use crate::cli::OverrideArgs;
+
+#[derive(Args, Debug)]
+pub struct ReversibilityArgs {
+    /// Path to the config file with the simulation details
+    #[arg(short, long)]
+    pub filename: String,
+
+    /// Format of the config file, taken from the file extension if not given
+    #[arg(long, value_enum)]
+    pub format: Option<ConfigFormat>,
+
+    /// Number of steps in each direction, defaults to `steps` of the config
+    #[arg(short, long)]
+    pub steps: Option<i64>,
+
+    /// Number of threads used for the force evaluation, overrides `threads` in the config
+    #[arg(short, long)]
+    pub threads: Option<usize>,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub overrides: OverrideArgs,
+}
+
+/// Runs a config forward and back and prints how far it ends up from the initial state
+pub fn reversibility_check(args: ReversibilityArgs) -> Result<(), ApplicationError> {
+    let configsystem = apply_overrides(
+        ConfigSystem::parse_as(args.filename, args.format)?,
+        &args.overrides.overrides,
+    )?;
+    let mut config = configsystem.config;
+    if args.threads.is_some() {
+        config.threads = args.threads;
+    }
+    let steps = args.steps.unwrap_or(config.steps);
+    let report = check_reversibility(
+        &config,
+        configsystem.system,
+        configsystem.test_particles,
+        steps,
+    )?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{report}");
+    }
+    Ok(())
+}
@@ -0,0 +1,597 @@
+use std::{
+    io::{BufRead, BufReader, Cursor, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, SyncSender, TryRecvError, TrySendError},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+use tungstenite::Message;
+
+use kepler_cli::{
+    configsystem::{ConfigFormat, ConfigSystem},
+    configvalue::apply_overrides,
+    error::ApplicationError,
+    export::export_state_to_jsonl,
+    frame::relative_to,
+    simulation::Simulation,
+};
+
+use crate::cli::OverrideArgs;
+
+/// The page that draws the orbits, it connects back to `/ws`
+const INDEX_HTML: &str = include_str!("serve.html");
+
+/// Frames a client may fall behind before frames are dropped for it
+const CLIENT_BUFFER: usize = 16;
+
+/// Longest request line and headers accepted before a connection is dropped
+const MAX_REQUEST_HEAD: usize = 16 * 1024;
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Path to the config file with the simulation details
+    #[arg(short, long)]
+    pub filename: String,
+
+    /// Format of the config file, taken from the file extension if not given
+    #[arg(long, value_enum)]
+    pub format: Option<ConfigFormat>,
+
+    /// Port of the web page on localhost
+    #[arg(short, long, default_value_t = 8080)]
+    pub port: u16,
+
+    /// Frames sent per second, every frame advances the simulation by `export_step` steps
+    #[arg(long, default_value_t = 30)]
+    pub fps: u32,
+
+    /// Number of threads used for the force evaluation, overrides `threads` in the config
+    #[arg(short, long)]
+    pub threads: Option<usize>,
+
+    /// Start paused, the simulation only advances when requested from the page
+    #[arg(long)]
+    pub paused: bool,
+
+    #[command(flatten)]
+    pub overrides: OverrideArgs,
+}
+
+/// What a page can ask the simulation to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ControlCommand {
+    Pause,
+    Resume,
+    /// Advance by one frame and stay paused
+    Step,
+}
+
+impl std::str::FromStr for ControlCommand {
+    type Err = String;
+
+    fn from_str(command: &str) -> Result<Self, Self::Err> {
+        match command.trim() {
+            "pause" => Ok(ControlCommand::Pause),
+            "resume" => Ok(ControlCommand::Resume),
+            "step" => Ok(ControlCommand::Step),
+            command => Err(format!("Unknown command `{command}`")),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Control {
+    paused: bool,
+    /// Frames requested with `step` while paused
+    requested_frames: u64,
+}
+
+impl Control {
+    fn apply(&mut self, command: ControlCommand) {
+        match command {
+            ControlCommand::Pause => self.paused = true,
+            ControlCommand::Resume => {
+                self.paused = false;
+                self.requested_frames = 0;
+            }
+            ControlCommand::Step => {
+                self.paused = true;
+                self.requested_frames += 1;
+            }
+        }
+    }
+
+    /// Whether the simulation may advance by a frame, using up a requested frame
+    /// while paused
+    fn take_frame(&mut self) -> bool {
+        if !self.paused {
+            true
+        } else if self.requested_frames > 0 {
+            self.requested_frames -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        if self.paused {
+            "paused"
+        } else {
+            "running"
+        }
+    }
+}
+
+/// State shared between the simulation and the connected pages
+#[derive(Default)]
+struct Shared {
+    control: Mutex<Control>,
+    control_changed: Condvar,
+    clients: Mutex<Vec<SyncSender<String>>>,
+    /// The last frame, sent to pages as soon as they connect
+    latest_frame: Mutex<Option<String>>,
+}
+
+impl Shared {
+    /// Sends a message to every page, pages that cannot keep up miss it
+    fn broadcast(&self, message: String) {
+        self.clients
+            .lock()
+            .expect("that the client list is not poisoned")
+            .retain(|client| match client.try_send(message.clone()) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+
+    fn broadcast_status(&self, status: &str) {
+        self.broadcast(serde_json::json!({ "status": status }).to_string());
+    }
+
+    /// Handles `pause`, `resume` and `step` sent by a page
+    fn command(&self, command: &str) {
+        let command: ControlCommand = match command.parse() {
+            Ok(command) => command,
+            Err(e) => {
+                tracing::event!(tracing::Level::WARN, "{e}");
+                return;
+            }
+        };
+        let mut control = self
+            .control
+            .lock()
+            .expect("that the control state is not poisoned");
+        control.apply(command);
+        let status = control.status();
+        drop(control);
+        self.control_changed.notify_all();
+        self.broadcast_status(status);
+    }
+
+    /// Blocks while the simulation is paused and no frame was requested
+    fn wait_for_frame(&self) {
+        let mut control = self
+            .control
+            .lock()
+            .expect("that the control state is not poisoned");
+        while !control.take_frame() {
+            control = self
+                .control_changed
+                .wait(control)
+                .expect("that the control state is not poisoned");
+        }
+    }
+}
+
+fn frame(simulation: &Simulation) -> Result<String, ApplicationError> {
+    let config = simulation.config();
+    let system = simulation.system();
+    let test_particles = simulation.test_particles();
+    let relative;
+    let (exported_system, exported_test_particles) = match &config.export_relative_to {
+        Some(name) => {
+            relative = relative_to(name, system, test_particles)?;
+            (&relative.0, relative.1.as_slice())
+        }
+        None => (system, test_particles),
+    };
+    let mut line = vec![];
+    export_state_to_jsonl(
+        config,
+        system,
+        exported_system,
+        exported_test_particles,
+        simulation.steps(),
+        simulation.time(),
+        &mut line,
+    )?;
+    Ok(String::from_utf8_lossy(&line).trim_end().to_owned())
+}
+
+/// Runs the simulation frame by frame, as fast as `fps` allows and only while not paused
+fn simulate(mut simulation: Simulation, fps: u32, shared: &Shared) -> Result<(), ApplicationError> {
+    let frame_interval = Duration::from_secs_f64(1.0 / f64::from(fps.max(1)));
+
+    let publish = |message: String| {
+        *shared
+            .latest_frame
+            .lock()
+            .expect("that the latest frame is not poisoned") = Some(message.clone());
+        shared.broadcast(message);
+    };
+    publish(frame(&simulation)?);
+
+    while !simulation.is_done() {
+        shared.wait_for_frame();
+        let frame_start = Instant::now();
+        let config = simulation.config();
+        for _ in 0..config.export_step.min(config.steps - simulation.steps()) {
+            simulation.step()?;
+        }
+        publish(frame(&simulation)?);
+        std::thread::sleep(frame_interval.saturating_sub(frame_start.elapsed()));
+    }
+    shared.broadcast_status("finished");
+    Ok(())
+}
+
+/// The request line and headers of a connection, up to the empty line
+#[derive(Debug, PartialEq, Eq)]
+struct RequestHead {
+    path: String,
+    /// Whether the client asks to upgrade the connection to a WebSocket
+    websocket: bool,
+    /// The origin of the page that opened the connection, browsers always send it
+    /// with a WebSocket request
+    origin: Option<String>,
+}
+
+impl RequestHead {
+    fn parse(head: &str) -> Option<Self> {
+        let mut lines = head.lines();
+        let path = lines.next()?.split_whitespace().nth(1)?.to_owned();
+        let mut request = Self {
+            path,
+            websocket: false,
+            origin: None,
+        };
+        for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+            let (name, value) = (name.trim(), value.trim());
+            if name.eq_ignore_ascii_case("upgrade") {
+                request.websocket = value.eq_ignore_ascii_case("websocket");
+            } else if name.eq_ignore_ascii_case("origin") {
+                request.origin = Some(value.to_owned());
+            }
+        }
+        Some(request)
+    }
+
+    /// Whether the request comes from the served page itself. Any page open in
+    /// the browser can connect to localhost, so WebSockets from other origins
+    /// must not read the simulation or control it.
+    fn is_from_own_page(&self, port: u16) -> bool {
+        self.origin.as_deref().is_some_and(|origin| {
+            ["127.0.0.1", "localhost"]
+                .iter()
+                .any(|host| origin == format!("http://{host}:{port}"))
+        })
+    }
+}
+
+/// Reads the request line and headers, however many packets they arrive in
+fn read_request_head(reader: &mut impl BufRead) -> Result<Vec<u8>, std::io::Error> {
+    let mut head = vec![];
+    loop {
+        let start = head.len();
+        let limit = (MAX_REQUEST_HEAD - start) as u64;
+        if reader.take(limit).read_until(b'\n', &mut head)? == 0 {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "the connection closed before the request was complete",
+            ));
+        }
+        if !head.ends_with(b"\n") {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "the request head is too long",
+            ));
+        }
+        let line = &head[start..];
+        if start > 0 && (line == b"\r\n" || line == b"\n") {
+            return Ok(head);
+        }
+    }
+}
+
+/// A connection that first replays the bytes already read from it, so the
+/// WebSocket handshake sees the whole request
+struct ReplayedStream {
+    head: Cursor<Vec<u8>>,
+    stream: TcpStream,
+}
+
+impl Read for ReplayedStream {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        match self.head.read(buffer)? {
+            0 => self.stream.read(buffer),
+            read => Ok(read),
+        }
+    }
+}
+
+impl Write for ReplayedStream {
+    fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+        self.stream.write(buffer)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Answers a plain HTTP request with the page
+fn handle_http(stream: TcpStream, path: &str) -> Result<(), std::io::Error> {
+    match path {
+        "/" | "/index.html" => respond(stream, "200 OK", "text/html; charset=utf-8", INDEX_HTML),
+        _ => respond(stream, "404 Not Found", "text/plain", "Not found"),
+    }
+}
+
+fn respond(
+    mut stream: TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<(), std::io::Error> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Forwards frames to one page and its commands to the simulation
+fn handle_websocket(stream: ReplayedStream, shared: &Shared) -> Result<(), ApplicationError> {
+    let mut websocket =
+        tungstenite::accept(stream).map_err(|e| ApplicationError::WebSocketError(e.to_string()))?;
+    // reading must not block sending frames, so only wait briefly for commands
+    websocket
+        .get_ref()
+        .stream
+        .set_read_timeout(Some(Duration::from_millis(10)))?;
+
+    let (sender, receiver) = mpsc::sync_channel(CLIENT_BUFFER);
+    if let Some(frame) = shared
+        .latest_frame
+        .lock()
+        .expect("that the latest frame is not poisoned")
+        .clone()
+    {
+        websocket.send(Message::Text(frame))?;
+    }
+    let status = shared
+        .control
+        .lock()
+        .expect("that the control state is not poisoned")
+        .status();
+    websocket.send(Message::Text(
+        serde_json::json!({ "status": status }).to_string(),
+    ))?;
+    shared
+        .clients
+        .lock()
+        .expect("that the client list is not poisoned")
+        .push(sender);
+
+    loop {
+        match websocket.read() {
+            Ok(Message::Text(command)) => shared.command(command.trim()),
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                return Ok(())
+            }
+            Err(e) => return Err(e.into()),
+        }
+        loop {
+            match receiver.try_recv() {
+                Ok(message) => websocket.send(Message::Text(message))?,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    shared: &Shared,
+    port: u16,
+) -> Result<(), ApplicationError> {
+    let mut reader = BufReader::new(stream);
+    let mut head = read_request_head(&mut reader)?;
+    let request = RequestHead::parse(&String::from_utf8_lossy(&head)).ok_or_else(|| {
+        std::io::Error::new(ErrorKind::InvalidData, "the request line is malformed")
+    })?;
+    if request.websocket && !request.is_from_own_page(port) {
+        respond(
+            reader.into_inner(),
+            "403 Forbidden",
+            "text/plain",
+            "Only the served page may connect",
+        )?;
+        tracing::event!(
+            tracing::Level::WARN,
+            "Rejected a WebSocket from origin {}",
+            request.origin.as_deref().unwrap_or("unknown")
+        );
+        Ok(())
+    } else if request.websocket {
+        // the reader may already hold bytes sent after the headers
+        head.extend_from_slice(reader.buffer());
+        let stream = ReplayedStream {
+            head: Cursor::new(head),
+            stream: reader.into_inner(),
+        };
+        handle_websocket(stream, shared)
+    } else {
+        Ok(handle_http(reader.into_inner(), &request.path)?)
+    }
+}
+
+/// Runs the simulation while serving a page on localhost that draws it live
+pub fn serve(args: ServeArgs) -> Result<(), ApplicationError> {
+    let configsystem = apply_overrides(
+        ConfigSystem::parse_as(args.filename, args.format)?,
+        &args.overrides.overrides,
+    )?;
+    let mut config = configsystem.config;
+    if args.threads.is_some() {
+        config.threads = args.threads;
+    }
+
+    let shared = Arc::new(Shared::default());
+    shared
+        .control
+        .lock()
+        .expect("that the control state is not poisoned")
+        .paused = args.paused;
+
+    // a simulation cannot be moved to another thread, so it is built in the one
+    // that runs it and only whether that worked is sent back
+    let simulation_shared = Arc::clone(&shared);
+    let (built, started) = mpsc::channel();
+    std::thread::spawn(move || {
+        let simulation = Simulation::builder(config, configsystem.system)
+            .test_particles(configsystem.test_particles)
+            .body_properties(configsystem.body_properties)
+            .exports(false)
+            .build();
+        let simulation = match simulation {
+            Ok(simulation) => {
+                let _ = built.send(Ok(()));
+                simulation
+            }
+            Err(e) => {
+                let _ = built.send(Err(e));
+                return;
+            }
+        };
+        match simulate(simulation, args.fps, &simulation_shared) {
+            Ok(()) => tracing::event!(tracing::Level::INFO, "Simulation finished 🥳"),
+            Err(e) => tracing::event!(tracing::Level::ERROR, "Simulation failed: {e}"),
+        }
+    });
+    started
+        .recv()
+        .expect("that the simulation thread reports whether it started")?;
+
+    let listener = TcpListener::bind(("127.0.0.1", args.port))?;
+    let address = listener.local_addr()?;
+    println!("Serving the simulation on http://{address}");
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::event!(tracing::Level::WARN, "Could not accept connection: {e}");
+                continue;
+            }
+        };
+        let shared = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &shared, address.port()) {
+                tracing::event!(tracing::Level::DEBUG, "Connection closed: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::VecDeque, io::BufReader};
+
+    use super::{read_request_head, Control, ControlCommand, RequestHead};
+
+    /// Hands out one packet per read, like a socket
+    struct Packets(VecDeque<&'static [u8]>);
+
+    impl std::io::Read for Packets {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let Some(packet) = self.0.pop_front() else {
+                return Ok(0);
+            };
+            buffer[..packet.len()].copy_from_slice(packet);
+            Ok(packet.len())
+        }
+    }
+
+    #[test]
+    pub fn reads_requests_split_into_packets() {
+        let mut reader = BufReader::new(Packets(VecDeque::from([
+            &b"GET /ws HTTP/1.1\r\nHost: localhost\r\n"[..],
+            &b"Connection: Upgrade\r\nUpgrade: websocket\r\nOrigin: http://localhost:8080\r\n"[..],
+            &b"\r\n"[..],
+        ])));
+        let head = read_request_head(&mut reader).unwrap();
+        assert_eq!(
+            RequestHead::parse(&String::from_utf8_lossy(&head)),
+            Some(RequestHead {
+                path: "/ws".to_owned(),
+                websocket: true,
+                origin: Some("http://localhost:8080".to_owned()),
+            })
+        );
+
+        let mut reader = BufReader::new(Packets(VecDeque::from([&b"GET / HTTP/1.1\r\n"[..]])));
+        assert!(read_request_head(&mut reader).is_err());
+    }
+
+    #[test]
+    pub fn accepts_websockets_only_from_the_served_page() {
+        let request = |origin: &str| {
+            RequestHead::parse(&format!(
+                "GET /ws HTTP/1.1\r\nUpgrade: websocket\r\n{origin}\r\n"
+            ))
+            .unwrap()
+        };
+        assert!(request("Origin: http://127.0.0.1:8080").is_from_own_page(8080));
+        assert!(request("origin: http://localhost:8080").is_from_own_page(8080));
+        assert!(!request("Origin: http://localhost:8081").is_from_own_page(8080));
+        assert!(!request("Origin: https://example.com").is_from_own_page(8080));
+        assert!(!request("Origin: null").is_from_own_page(8080));
+        assert!(!request("").is_from_own_page(8080));
+    }
+
+    #[test]
+    pub fn pauses_steps_and_resumes() {
+        let command = |text: &str| text.parse::<ControlCommand>();
+        assert_eq!(command(" step\n"), Ok(ControlCommand::Step));
+        assert!(command("faster").is_err());
+
+        let mut control = Control::default();
+        assert!(control.take_frame());
+        control.apply(command("pause").unwrap());
+        assert!(!control.take_frame());
+        // every step advances by exactly one frame
+        control.apply(command("step").unwrap());
+        control.apply(command("step").unwrap());
+        assert!(control.take_frame());
+        assert!(control.take_frame());
+        assert!(!control.take_frame());
+        assert_eq!(control.status(), "paused");
+        control.apply(command("resume").unwrap());
+        assert!(control.take_frame());
+        assert!(control.take_frame());
+        assert_eq!(control.status(), "running");
+    }
+}
@@ -0,0 +1,378 @@
+use std::{
+    fs::DirBuilder,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use clap::Args;
+use kepler_core::{
+    center_of_mass::calculate_center_of_mass, impulse::calculate_total_impulse, types::System,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+use toml::Value;
+
+use kepler_cli::{
+    configsystem::{ConfigFormat, ConfigSystem},
+    configvalue::{apply_overrides, get_value, set_value},
+    diagnostics::calculate_total_energy,
+    error::ApplicationError,
+    simulation::Simulation,
+    summary::RunSummary,
+};
+
+use crate::cli::OverrideArgs;
+
+#[derive(Args, Debug)]
+pub struct SweepArgs {
+    /// Path to the config file with the base simulation details
+    #[arg(short, long)]
+    pub filename: String,
+
+    /// Format of the config file, taken from the file extension if not given
+    #[arg(long, value_enum)]
+    pub format: Option<ConfigFormat>,
+
+    /// Path to the toml file describing the parameters to vary
+    #[arg(short = 'p', long)]
+    pub spec: String,
+
+    /// Number of simulations to run at the same time, defaults to the number of cores
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+
+    #[command(flatten)]
+    pub overrides: OverrideArgs,
+}
+
+/// The sweep spec file
+///
+/// ```toml
+/// seed = 1
+///
+/// [[parameters]]
+/// path = "config.timestep"
+/// log = { start = 100.0, end = 1000.0, count = 4 }
+///
+/// [[parameters]]
+/// path = "system.bodies[Earth].velocity.y"
+/// random = { relative = 0.01, samples = 10 }
+/// ```
+///
+/// Every combination of the parameter values is run.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SweepSpec {
+    #[serde(default)]
+    pub seed: u64,
+    pub parameters: Vec<SweepParameter>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SweepParameter {
+    pub path: String,
+    #[serde(flatten)]
+    pub values: SweepValues,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SweepValues {
+    /// `count` evenly spaced values from `start` to `end`
+    Linear { start: f64, end: f64, count: usize },
+    /// `count` logarithmically spaced values from `start` to `end`
+    Log { start: f64, end: f64, count: usize },
+    /// The given values
+    Values(Vec<Value>),
+    /// `samples` values drawn uniformly within ± `relative` around the base value
+    Random { relative: f64, samples: usize },
+}
+
+fn spaced(start: f64, end: f64, count: usize) -> Vec<f64> {
+    match count {
+        0 => vec![],
+        1 => vec![start],
+        _ => (0..count)
+            .map(|i| start + (end - start) * i as f64 / (count - 1) as f64)
+            .collect(),
+    }
+}
+
+impl SweepParameter {
+    fn expand(
+        &self,
+        base: &ConfigSystem,
+        rng: &mut ChaCha8Rng,
+    ) -> Result<Vec<Value>, ApplicationError> {
+        let values = match &self.values {
+            SweepValues::Linear { start, end, count } => spaced(*start, *end, *count)
+                .into_iter()
+                .map(Value::Float)
+                .collect(),
+            SweepValues::Log { start, end, count } => spaced(start.ln(), end.ln(), *count)
+                .into_iter()
+                .map(|value| Value::Float(value.exp()))
+                .collect(),
+            SweepValues::Values(values) => values.clone(),
+            SweepValues::Random { relative, samples } => {
+                let base_value = match get_value(base, &self.path)? {
+                    Value::Float(value) => value,
+                    Value::Integer(value) => value as f64,
+                    other => {
+                        return Err(ApplicationError::ConfigPathError(format!(
+                            "`{}` is {other}, only numbers can be perturbed",
+                            self.path
+                        )))
+                    }
+                };
+                (0..*samples)
+                    .map(|_| {
+                        Value::Float(base_value * (1.0 + rng.gen_range(-relative..=*relative)))
+                    })
+                    .collect()
+            }
+        };
+        Ok(values)
+    }
+}
+
+/// One run of the sweep: the values of all parameters, in spec order
+#[derive(Debug, Clone)]
+struct Variant {
+    values: Vec<Value>,
+    configsystem: ConfigSystem,
+}
+
+fn build_variants(base: &ConfigSystem, spec: &SweepSpec) -> Result<Vec<Variant>, ApplicationError> {
+    let mut rng = ChaCha8Rng::seed_from_u64(spec.seed);
+    let mut variants = vec![Variant {
+        values: vec![],
+        configsystem: base.clone(),
+    }];
+    for parameter in spec.parameters.iter() {
+        let values = parameter.expand(base, &mut rng)?;
+        let mut next = Vec::with_capacity(variants.len() * values.len());
+        for variant in variants.iter() {
+            for value in values.iter() {
+                let mut values = variant.values.clone();
+                values.push(value.clone());
+                next.push(Variant {
+                    values,
+                    configsystem: set_value(&variant.configsystem, &parameter.path, value.clone())?,
+                });
+            }
+        }
+        variants = next;
+    }
+    Ok(variants)
+}
+
+/// The final state and the summary of one run, or why it failed
+type RunResult = Result<(System, RunSummary), String>;
+
+fn run_variant(variant: &Variant) -> RunResult {
+    let configsystem = variant.configsystem.clone();
+    Simulation::builder(configsystem.config, configsystem.system)
+        .test_particles(configsystem.test_particles)
+        .body_properties(configsystem.body_properties)
+        .build()
+        .and_then(Simulation::run)
+        .map_err(|e| e.to_string())
+}
+
+pub fn sweep(args: SweepArgs) -> Result<(), ApplicationError> {
+    let base = apply_overrides(
+        ConfigSystem::parse_as(args.filename, args.format)?,
+        &args.overrides.overrides,
+    )?;
+    let spec: SweepSpec = toml::from_str(&std::fs::read_to_string(&args.spec)?)?;
+
+    let mut variants = build_variants(&base, &spec)?;
+    let export_directory = Path::new(&base.config.export_directory);
+    for (index, variant) in variants.iter_mut().enumerate() {
+        variant.configsystem.config.export_directory = export_directory
+            .join(format!("run_{index:04}"))
+            .to_string_lossy()
+            .into_owned();
+    }
+
+    let jobs = args
+        .jobs
+        .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .clamp(1, variants.len().max(1));
+    tracing::event!(
+        tracing::Level::INFO,
+        "Running {} variants on {} threads",
+        variants.len(),
+        jobs
+    );
+
+    // every worker picks the next variant that has not been started yet
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, RunResult)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(variant) = variants.get(index) else {
+                            break;
+                        };
+                        results.push((index, run_variant(variant)));
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("that a sweep worker does not panic"))
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+
+    if !export_directory.exists() {
+        DirBuilder::new().recursive(true).create(export_directory)?;
+    }
+    let summary_path = export_directory.join(format!(
+        "{}_sweep_summary.csv",
+        base.config.export_file_name_prefix
+    ));
+    write_summary(&summary_path, &spec, &variants, &results)?;
+    tracing::event!(
+        tracing::Level::INFO,
+        "Wrote sweep summary to {}",
+        summary_path.display()
+    );
+    Ok(())
+}
+
+fn write_summary(
+    path: &Path,
+    spec: &SweepSpec,
+    variants: &[Variant],
+    results: &[(usize, RunResult)],
+) -> Result<(), ApplicationError> {
+    let mut wtr = csv::Writer::from_path(path)?;
+
+    let mut headers: Vec<String> = vec!["Run".to_owned(), "Directory".to_owned()];
+    headers.extend(spec.parameters.iter().map(|p| p.path.clone()));
+    headers.extend(
+        [
+            "Initial energy",
+            "Final energy",
+            "Max relative energy error",
+            "Momentum drift",
+            "Impulse x",
+            "Impulse y",
+            "Center of mass x",
+            "Center of mass y",
+            "Closest approach",
+            "Wall-clock seconds",
+            "Error",
+        ]
+        .map(|s| s.to_owned()),
+    );
+    wtr.write_record(&headers)?;
+
+    for (index, result) in results {
+        let variant = &variants[*index];
+        let mut record = vec![
+            index.to_string(),
+            variant.configsystem.config.export_directory.clone(),
+        ];
+        record.extend(variant.values.iter().map(|value| match value {
+            Value::String(s) => s.clone(),
+            value => value.to_string(),
+        }));
+        match result {
+            Ok((system, summary)) => {
+                let impulse = calculate_total_impulse(system);
+                let center_of_mass = calculate_center_of_mass(system);
+                record.extend(
+                    [
+                        summary.initial_energy,
+                        summary.final_energy,
+                        summary.max_relative_energy_error,
+                        summary.momentum_drift,
+                        impulse.x,
+                        impulse.y,
+                        center_of_mass.x,
+                        center_of_mass.y,
+                        summary
+                            .closest_approach
+                            .as_ref()
+                            .map_or(f64::NAN, |closest| closest.distance),
+                        summary.wall_clock_seconds,
+                    ]
+                    .map(|value| format!("{value:e}")),
+                );
+                record.push(String::new());
+            }
+            Err(e) => {
+                let initial_energy = calculate_total_energy(&variant.configsystem.system);
+                record.push(format!("{initial_energy:e}"));
+                record.extend(vec![String::new(); 9]);
+                record.push(e.clone());
+            }
+        }
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{build_variants, SweepSpec};
+    use kepler_cli::configsystem::ConfigSystem;
+
+    #[test]
+    pub fn builds_every_combination_of_parameters() {
+        let base: ConfigSystem = toml::from_str(
+            r#"
+            [config]
+            timestep = 1.0
+            steps = 10
+            export_step = 1
+            export_directory = "export_files"
+            export_file_name_prefix = "SIM"
+
+            [[system.bodies]]
+            name = "Earth"
+            mass = 5.972e24
+            position = { x = 1.5e11, y = 0.0 }
+            velocity = { x = 0.0, y = 3.0e4 }
+        "#,
+        )
+        .unwrap();
+        let spec: SweepSpec = toml::from_str(
+            r#"
+            [[parameters]]
+            path = "config.timestep"
+            log = { start = 1.0, end = 100.0, count = 3 }
+
+            [[parameters]]
+            path = "system.bodies[Earth].velocity.y"
+            random = { relative = 0.1, samples = 2 }
+        "#,
+        )
+        .unwrap();
+
+        let variants = build_variants(&base, &spec).unwrap();
+        assert_eq!(variants.len(), 6);
+        assert!((variants[2].configsystem.config.timestep - 10.0).abs() < 1e-9);
+        assert!((variants[5].configsystem.config.timestep - 100.0).abs() < 1e-9);
+        for variant in variants.iter() {
+            let velocity = variant.configsystem.system.bodies[0].velocity.y;
+            assert!((2.7e4..=3.3e4).contains(&velocity));
+        }
+        // the perturbations are drawn once per parameter, not once per run
+        assert_eq!(
+            variants[0].configsystem.system.bodies[0].velocity.y,
+            variants[2].configsystem.system.bodies[0].velocity.y
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    collections::VecDeque,
+    io::{Stderr, Write},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use kepler_core::types::System;
+use maths_rs::num::Cast;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style},
+    symbols::Marker,
+    text::Line,
+    widgets::{
+        canvas::{Canvas, Points},
+        Block, Borders, Gauge, Paragraph, Sparkline,
+    },
+    Frame, Terminal,
+};
+
+use kepler_cli::{
+    configsystem::{Config, TestParticle},
+    diagnostics::calculate_total_energy,
+    simulation::{format_signed_time, format_time, Monitor},
+};
+
+/// The view is redrawn at most this often, the simulation runs freely in between
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+/// How long to wait for a key while paused
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Number of past positions drawn behind each body
+const TRAIL_LENGTH: usize = 300;
+/// Number of energy error samples kept for the sparkline
+const ENERGY_HISTORY_LENGTH: usize = 1000;
+const COLORS: [Color; 6] = [
+    Color::Yellow,
+    Color::Cyan,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightMagenta,
+    Color::LightBlue,
+];
+
+/// The log lines written while the view is shown, `None` while they go to stderr
+static HELD_LOGS: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+/// The writer of the log, which holds the lines back while the view is drawn on
+/// stderr so they do not draw over it
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut held_logs = HELD_LOGS.lock().unwrap_or_else(PoisonError::into_inner);
+        match held_logs.as_mut() {
+            Some(held_logs) => {
+                held_logs.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            None => std::io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
+
+/// Holds the log lines back until it is dropped, then writes them to stderr
+struct HeldLogs;
+
+impl HeldLogs {
+    fn hold() -> Self {
+        *HELD_LOGS.lock().unwrap_or_else(PoisonError::into_inner) = Some(vec![]);
+        HeldLogs
+    }
+}
+
+impl Drop for HeldLogs {
+    fn drop(&mut self) {
+        let held_logs = HELD_LOGS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(held_logs) = held_logs {
+            // nothing sensible can be done if stderr is gone
+            let _ = std::io::stderr().write_all(&held_logs);
+        }
+    }
+}
+
+/// The part of the plane shown in the terminal
+struct View {
+    center: (f64, f64),
+    /// Half the height of the view in meters
+    extent: f64,
+    /// Index of the body the view is centered on
+    follow: Option<usize>,
+}
+
+/// Shows the running simulation in the terminal
+///
+/// The terminal is restored when the `Tui` is dropped, and the log lines written
+/// through [`LogWriter`] in the meantime are printed after it.
+pub struct Tui {
+    terminal: Terminal<CrosstermBackend<Stderr>>,
+    /// Dropped after the terminal is restored
+    _held_logs: HeldLogs,
+    start: Instant,
+    last_draw: Option<Instant>,
+    initial_energy: f64,
+    energy_errors: VecDeque<f64>,
+    trails: Vec<VecDeque<(f64, f64)>>,
+    view: View,
+    paused: bool,
+}
+
+fn extent_of(system: &System) -> f64 {
+    let extent = system
+        .bodies
+        .iter()
+        .map(|body| body.position.x.abs().max(body.position.y.abs()))
+        .fold(0.0, f64::max);
+    if extent > 0.0 {
+        extent * 1.1
+    } else {
+        1.0
+    }
+}
+
+impl Tui {
+    /// Switches the terminal to the live view, drawing on stderr so stdout can still be streamed
+    pub fn new() -> Result<Self, std::io::Error> {
+        let held_logs = HeldLogs::hold();
+        enable_raw_mode()?;
+        let mut stderr = std::io::stderr();
+        stderr.execute(EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stderr))?;
+        terminal.clear()?;
+        Ok(Self {
+            terminal,
+            _held_logs: held_logs,
+            start: Instant::now(),
+            last_draw: None,
+            initial_energy: 0.0,
+            energy_errors: VecDeque::with_capacity(ENERGY_HISTORY_LENGTH),
+            trails: vec![],
+            view: View {
+                center: (0.0, 0.0),
+                extent: 1.0,
+                follow: None,
+            },
+            paused: false,
+        })
+    }
+
+    fn record(&mut self, system: &System) {
+        let energy = calculate_total_energy(system);
+        let error = if self.initial_energy == 0.0 {
+            energy.abs()
+        } else {
+            ((energy - self.initial_energy) / self.initial_energy).abs()
+        };
+        if self.energy_errors.len() == ENERGY_HISTORY_LENGTH {
+            self.energy_errors.pop_front();
+        }
+        self.energy_errors.push_back(error);
+
+        self.trails.resize_with(system.bodies.len(), VecDeque::new);
+        for (trail, body) in self.trails.iter_mut().zip(system.bodies.iter()) {
+            if trail.len() == TRAIL_LENGTH {
+                trail.pop_front();
+            }
+            trail.push_back((body.position.x, body.position.y));
+        }
+    }
+
+    /// Handles all pending keys, returns `false` when the user quits
+    fn handle_events(&mut self, system: &System) -> Result<bool, std::io::Error> {
+        let mut timeout = match self.paused {
+            true => PAUSED_POLL_INTERVAL,
+            false => Duration::ZERO,
+        };
+        while event::poll(timeout)? {
+            timeout = Duration::ZERO;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let pan = self.view.extent * 0.1;
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Char(' ') | KeyCode::Char('p') => self.paused = !self.paused,
+                KeyCode::Char('+') | KeyCode::Char('=') => self.view.extent /= 1.5,
+                KeyCode::Char('-') => self.view.extent *= 1.5,
+                KeyCode::Left => self.pan(system, -pan, 0.0),
+                KeyCode::Right => self.pan(system, pan, 0.0),
+                KeyCode::Up => self.pan(system, 0.0, pan),
+                KeyCode::Down => self.pan(system, 0.0, -pan),
+                KeyCode::Char('f') => {
+                    self.view.follow = match self.view.follow {
+                        None if !system.bodies.is_empty() => Some(0),
+                        Some(index) if index + 1 < system.bodies.len() => Some(index + 1),
+                        _ => None,
+                    }
+                }
+                KeyCode::Char('c') => {
+                    self.view = View {
+                        center: (0.0, 0.0),
+                        extent: extent_of(system),
+                        follow: None,
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(true)
+    }
+
+    /// Moves the view, a followed body is no longer followed
+    fn pan(&mut self, system: &System, dx: f64, dy: f64) {
+        let (x, y) = self.center(system);
+        self.view.center = (x + dx, y + dy);
+        self.view.follow = None;
+    }
+
+    fn center(&self, system: &System) -> (f64, f64) {
+        match self.view.follow.and_then(|index| system.bodies.get(index)) {
+            Some(body) => (body.position.x, body.position.y),
+            None => self.view.center,
+        }
+    }
+
+    fn draw(
+        &mut self,
+        config: &Config,
+        system: &System,
+        test_particles: &[TestParticle],
+        step: i64,
+        time: f64,
+    ) -> Result<(), std::io::Error> {
+        let center = self.center(system);
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let steps_per_second = step.as_f64() / elapsed;
+        let remaining = (config.steps - step).as_f64() / steps_per_second;
+        let progress = format!(
+            "{:.1}% · step {step}/{} · {} simulated · {steps_per_second:.0} steps/s · ETA {}",
+            step.as_f64() / config.steps.as_f64() * 100.0,
+            config.steps,
+            format_signed_time(time),
+            format_time(remaining.as_u64())
+        );
+        let followed = self
+            .view
+            .follow
+            .and_then(|index| system.bodies.get(index))
+            .map_or(String::new(), |body| format!(" following {}", body.name));
+        let status = if self.paused { " · PAUSED" } else { "" };
+
+        let view = &self.view;
+        let trails = &self.trails;
+        let energy_errors = &self.energy_errors;
+        self.terminal.draw(|frame: &mut Frame| {
+            let [view_area, progress_area, energy_area, help_area] = Layout::vertical([
+                Constraint::Min(5),
+                Constraint::Length(3),
+                Constraint::Length(5),
+                Constraint::Length(1),
+            ])
+            .areas(frame.size());
+
+            draw_system(
+                frame,
+                view_area,
+                system,
+                test_particles,
+                trails,
+                center,
+                view.extent,
+                format!(" {:e} m across{followed}{status} ", 2.0 * view.extent),
+            );
+
+            frame.render_widget(
+                Gauge::default()
+                    .block(Block::default().borders(Borders::ALL).title(" Progress "))
+                    .gauge_style(Style::default().fg(Color::Green))
+                    .ratio((step.as_f64() / config.steps.as_f64()).clamp(0.0, 1.0))
+                    .label(progress),
+                progress_area,
+            );
+
+            // the sparkline only takes integers, so the errors are scaled to the largest one shown
+            let shown = (energy_area.width.saturating_sub(2) as usize).min(energy_errors.len());
+            let errors: Vec<f64> = energy_errors
+                .iter()
+                .skip(energy_errors.len() - shown)
+                .copied()
+                .collect();
+            let max_error = errors.iter().copied().fold(0.0, f64::max);
+            let data: Vec<u64> = errors
+                .iter()
+                .map(|error| {
+                    if max_error == 0.0 {
+                        0
+                    } else {
+                        (error / max_error * 1000.0).as_u64()
+                    }
+                })
+                .collect();
+            frame.render_widget(
+                Sparkline::default()
+                    .block(Block::default().borders(Borders::ALL).title(format!(
+                        " Relative energy error {:e} (max. shown {max_error:e}) ",
+                        errors.last().copied().unwrap_or(0.0)
+                    )))
+                    .style(Style::default().fg(Color::Magenta))
+                    .max(1000)
+                    .data(&data),
+                energy_area,
+            );
+
+            frame.render_widget(
+                Paragraph::new(
+                    "q quit · space pause · +/- zoom · arrows pan · f follow next body · c reset view",
+                ),
+                help_area,
+            );
+        })?;
+        Ok(())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_system(
+    frame: &mut Frame,
+    area: Rect,
+    system: &System,
+    test_particles: &[TestParticle],
+    trails: &[VecDeque<(f64, f64)>],
+    center: (f64, f64),
+    extent: f64,
+    title: String,
+) {
+    // braille characters have 2x4 dots and are about twice as high as wide
+    let aspect = f64::from(area.width.saturating_sub(2))
+        / f64::from(area.height.saturating_sub(2).max(1))
+        / 2.0;
+    let particles: Vec<(f64, f64)> = test_particles
+        .iter()
+        .map(|particle| (particle.position.x, particle.position.y))
+        .collect();
+    let canvas = Canvas::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .marker(Marker::Braille)
+        .x_bounds([center.0 - extent * aspect, center.0 + extent * aspect])
+        .y_bounds([center.1 - extent, center.1 + extent])
+        .paint(|context| {
+            context.draw(&Points {
+                coords: &particles,
+                color: Color::DarkGray,
+            });
+            for (index, trail) in trails.iter().enumerate() {
+                let trail: Vec<(f64, f64)> = trail.iter().copied().collect();
+                context.draw(&Points {
+                    coords: &trail,
+                    color: COLORS[index % COLORS.len()],
+                });
+            }
+            context.layer();
+            for (index, body) in system.bodies.iter().enumerate() {
+                context.print(
+                    body.position.x,
+                    body.position.y,
+                    Line::styled(
+                        format!("● {}", body.name),
+                        Style::default().fg(COLORS[index % COLORS.len()]),
+                    ),
+                );
+            }
+        });
+    frame.render_widget(canvas, area);
+}
+
+impl Monitor for Tui {
+    fn start(&mut self, _config: &Config, system: &System, _test_particles: &[TestParticle]) {
+        self.start = Instant::now();
+        self.initial_energy = calculate_total_energy(system);
+        self.view.extent = extent_of(system);
+    }
+
+    fn step(
+        &mut self,
+        config: &Config,
+        system: &System,
+        test_particles: &[TestParticle],
+        step: i64,
+        time: f64,
+    ) -> bool {
+        let due = self
+            .last_draw
+            .is_none_or(|last_draw| last_draw.elapsed() >= FRAME_INTERVAL);
+        if !due && step < config.steps {
+            return true;
+        }
+        self.record(system);
+        loop {
+            let result = self
+                .draw(config, system, test_particles, step, time)
+                .and_then(|_| self.handle_events(system));
+            self.last_draw = Some(Instant::now());
+            match result {
+                Ok(true) if self.paused => continue,
+                Ok(running) => return running,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "Terminal UI failed: {e}");
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        // nothing sensible can be done if the terminal cannot be restored
+        let _ = disable_raw_mode();
+        let _ = self.terminal.backend_mut().execute(LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
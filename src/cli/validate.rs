@@ -0,0 +1,90 @@
+use clap::Args;
+use serde::Serialize;
+
+use kepler_cli::{
+    configsystem::{ConfigFormat, ConfigSystem},
+    configvalue::apply_overrides,
+    error::ApplicationError,
+    simulation::format_signed_time,
+    stability::{check_stability, StabilityReport},
+};
+
+use crate::cli::OverrideArgs;
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    /// Path to the config file to check
+    #[arg(short, long)]
+    pub filename: String,
+
+    /// Format of the config file, taken from the file extension if not given
+    #[arg(long, value_enum)]
+    pub format: Option<ConfigFormat>,
+
+    /// Fail if the system is obviously unstable, like `strict_stability` in the config
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub overrides: OverrideArgs,
+}
+
+/// What a config describes and whether it is obviously unstable
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub bodies: usize,
+    pub test_particles: usize,
+    pub timestep: f64,
+    pub steps: i64,
+    pub simulated_time: f64,
+    pub stability: StabilityReport,
+}
+
+impl std::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Bodies          {}", self.bodies)?;
+        writeln!(f, "Test particles  {}", self.test_particles)?;
+        writeln!(f, "Timestep        {:e} s", self.timestep)?;
+        writeln!(f, "Steps           {}", self.steps)?;
+        writeln!(
+            f,
+            "Simulated time  {} ({:e} s)",
+            format_signed_time(self.simulated_time),
+            self.simulated_time
+        )?;
+        write!(f, "{}", self.stability)
+    }
+}
+
+/// Reads a config, prints what it describes and checks the stability of the system
+pub fn validate(args: ValidateArgs) -> Result<(), ApplicationError> {
+    let configsystem = apply_overrides(
+        ConfigSystem::parse_as(args.filename, args.format)?,
+        &args.overrides.overrides,
+    )?;
+    let config = &configsystem.config;
+    let report = ValidationReport {
+        bodies: configsystem.system.bodies.len(),
+        test_particles: configsystem.test_particles.len(),
+        timestep: config.signed_timestep(),
+        steps: config.steps,
+        simulated_time: config.steps as f64 * config.signed_timestep(),
+        stability: check_stability(&configsystem.system, &configsystem.body_properties),
+    };
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{report}");
+    }
+
+    if (args.strict || config.strict_stability) && report.stability.is_unstable() {
+        return Err(ApplicationError::UnstableSystemError(
+            "the stability checks failed".to_owned(),
+        ));
+    }
+    Ok(())
+}
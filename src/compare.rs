@@ -0,0 +1,252 @@
+use serde::Serialize;
+
+use crate::trajectory::{State, Trajectories};
+
+/// Exported times that differ by less than this fraction are taken as the same time
+const TIME_TOLERANCE: f64 = 1e-9;
+
+/// The divergence of one body at one time exported by both runs
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Divergence {
+    /// The step of the first run
+    pub step: i64,
+    pub time: f64,
+    pub position: f64,
+    pub velocity: f64,
+}
+
+/// How far a body of the second run drifted from the same body of the first run
+#[derive(Debug, Clone, Serialize)]
+pub struct BodyDivergence {
+    pub name: String,
+    pub max_position_divergence: f64,
+    pub max_velocity_divergence: f64,
+    /// Time of the largest position divergence
+    pub time_of_max: f64,
+    /// Time at which the position divergence first exceeded the threshold
+    pub threshold_exceeded_at: Option<f64>,
+    pub divergence: Vec<Divergence>,
+}
+
+/// The divergence of the bodies of two runs, compared at the times both exported
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    pub threshold: Option<f64>,
+    pub bodies: Vec<BodyDivergence>,
+    pub only_in_first: Vec<String>,
+    pub only_in_second: Vec<String>,
+}
+
+fn same_time(a: f64, b: f64) -> bool {
+    (a - b).abs() <= TIME_TOLERANCE * a.abs().max(b.abs()).max(1.0)
+}
+
+/// The state exported at `time`, from states sorted by time
+fn state_at<'a>(states: &[&'a State], time: f64) -> Option<&'a State> {
+    let index = states.partition_point(|state| state.time < time);
+    [index.checked_sub(1), Some(index)]
+        .into_iter()
+        .flatten()
+        .filter_map(|index| states.get(index))
+        .find(|state| same_time(state.time, time))
+        .copied()
+}
+
+/// Compares the bodies both runs exported at the times both runs exported, so
+/// runs with different timesteps or export steps are compared at the same times.
+/// `threshold` is the position divergence whose first crossing is reported.
+pub fn compare_trajectories(
+    first: &Trajectories,
+    second: &Trajectories,
+    threshold: Option<f64>,
+) -> Comparison {
+    let mut bodies = vec![];
+    for (name, first_states) in first.iter() {
+        let Some(second_states) = second.get(name) else {
+            continue;
+        };
+        let mut second_states: Vec<&State> = second_states.values().collect();
+        second_states.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let divergence: Vec<Divergence> = first_states
+            .iter()
+            .filter_map(|(step, a)| {
+                let b = state_at(&second_states, a.time)?;
+                Some(Divergence {
+                    step: *step,
+                    time: a.time,
+                    position: (b.x - a.x).hypot(b.y - a.y),
+                    velocity: (b.vx - a.vx).hypot(b.vy - a.vy),
+                })
+            })
+            .collect();
+        let max = divergence
+            .iter()
+            .copied()
+            .max_by(|a, b| a.position.total_cmp(&b.position));
+        bodies.push(BodyDivergence {
+            name: name.clone(),
+            max_position_divergence: max.map_or(0.0, |max| max.position),
+            max_velocity_divergence: divergence
+                .iter()
+                .map(|divergence| divergence.velocity)
+                .fold(0.0, f64::max),
+            time_of_max: max.map_or(0.0, |max| max.time),
+            threshold_exceeded_at: threshold.and_then(|threshold| {
+                divergence
+                    .iter()
+                    .find(|divergence| divergence.position > threshold)
+                    .map(|divergence| divergence.time)
+            }),
+            divergence,
+        });
+    }
+    let missing = |from: &Trajectories, other: &Trajectories| {
+        from.keys()
+            .filter(|name| !other.contains_key(*name))
+            .cloned()
+            .collect()
+    };
+    Comparison {
+        threshold,
+        bodies,
+        only_in_first: missing(first, second),
+        only_in_second: missing(second, first),
+    }
+}
+
+impl Comparison {
+    /// The earliest time at which a body exceeded the threshold
+    pub fn threshold_exceeded_at(&self) -> Option<f64> {
+        self.bodies
+            .iter()
+            .filter_map(|body| body.threshold_exceeded_at)
+            .min_by(f64::total_cmp)
+    }
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .bodies
+            .iter()
+            .map(|body| body.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("Body".len());
+        writeln!(
+            f,
+            "{:<width$}  {:>7}  {:>18}  {:>18}  {:>14}  {:>14}",
+            "Body", "Steps", "Max. Δ position/m", "Max. Δ velocity", "At time/s", "Exceeded at/s"
+        )?;
+        for body in self.bodies.iter() {
+            writeln!(
+                f,
+                "{:<width$}  {:>7}  {:>18.6e}  {:>14.6e} m/s  {:>14.6e}  {:>14}",
+                body.name,
+                body.divergence.len(),
+                body.max_position_divergence,
+                body.max_velocity_divergence,
+                body.time_of_max,
+                body.threshold_exceeded_at
+                    .map_or("-".to_owned(), |time| format!("{time:.6e}")),
+            )?;
+        }
+        if let Some(threshold) = self.threshold {
+            match self.threshold_exceeded_at() {
+                Some(time) => writeln!(
+                    f,
+                    "The divergence first exceeded {threshold:e} m at {time:e} s"
+                )?,
+                None => writeln!(f, "The divergence stayed below {threshold:e} m")?,
+            }
+        }
+        for (names, run) in [
+            (&self.only_in_first, "first"),
+            (&self.only_in_second, "second"),
+        ] {
+            if !names.is_empty() {
+                writeln!(f, "Only in the {run} run: {}", names.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::compare_trajectories;
+    use crate::{
+        configsystem::Config, export::export_system_to_csv_by_body, trajectory::read_trajectories,
+    };
+    use kepler_core::types::{Body, System};
+    use maths_rs::Vec2d;
+
+    /// Exports a body that moves by `1 + drift` m every 10 s
+    fn export(directory: &std::path::Path, timestep: f64, steps: i64, drift: f64) {
+        let contents = format!(
+            r#"
+            timestep = {timestep}
+            steps = {steps}
+            export_step = 1
+            export_directory = "{}"
+            export_file_name_prefix = "RUN"
+            "#,
+            directory.display()
+        );
+        let config: Config = toml::from_str(&contents).unwrap();
+        for step in 0..=steps {
+            let time = step as f64 * timestep;
+            let system = System {
+                bodies: vec![Body {
+                    name: "Moon".to_owned(),
+                    mass: 7.3e22,
+                    position: Vec2d::new(time / 10.0 * (1.0 + drift), 0.0),
+                    velocity: Vec2d::new(1.0, 0.0),
+                }],
+            };
+            export_system_to_csv_by_body(&config, &system, step, time).unwrap();
+        }
+    }
+
+    #[test]
+    pub fn reports_divergence_of_exported_histories() {
+        let directory =
+            std::env::temp_dir().join(format!("kepler_cli_compare_{}", std::process::id()));
+        export(&directory.join("first"), 10.0, 3, 0.0);
+        export(&directory.join("second"), 10.0, 3, 0.5);
+
+        let first = read_trajectories(directory.join("first"), "RUN").unwrap();
+        let second = read_trajectories(directory.join("second"), "RUN").unwrap();
+        let comparison = compare_trajectories(&first, &second, Some(0.9));
+        let moon = &comparison.bodies[0];
+        assert_eq!(moon.name, "Moon");
+        assert_eq!(moon.divergence.len(), 4);
+        assert_eq!(moon.max_position_divergence, 1.5);
+        assert_eq!(moon.time_of_max, 30.0);
+        assert_eq!(moon.threshold_exceeded_at, Some(20.0));
+        assert!(read_trajectories(directory.join("first"), "SIM").is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    pub fn compares_runs_with_different_timesteps_at_the_same_times() {
+        let directory =
+            std::env::temp_dir().join(format!("kepler_cli_compare_dt_{}", std::process::id()));
+        export(&directory.join("coarse"), 10.0, 3, 0.0);
+        export(&directory.join("fine"), 2.5, 12, 0.5);
+
+        let coarse = read_trajectories(directory.join("coarse"), "RUN").unwrap();
+        let fine = read_trajectories(directory.join("fine"), "RUN").unwrap();
+        let comparison = compare_trajectories(&coarse, &fine, None);
+        let moon = &comparison.bodies[0];
+        // the fine run is compared at the times of the coarse run, not its steps
+        let times: Vec<f64> = moon.divergence.iter().map(|d| d.time).collect();
+        assert_eq!(times, [0.0, 10.0, 20.0, 30.0]);
+        assert_eq!(moon.divergence[3].step, 3);
+        assert_eq!(moon.max_position_divergence, 1.5);
+        assert_eq!(moon.time_of_max, 30.0);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
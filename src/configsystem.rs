@@ -1,8 +1,9 @@
-use kepler_core::types::System;
+use kepler_core::types::{Body, System};
+use maths_rs::Vec2d;
 use serde::{Deserialize, Serialize};
-use std::{fs::File, io::Read};
+use std::{collections::BTreeMap, fs::File, io::Read, path::Path};
 
-use crate::error::ApplicationError;
+use crate::{error::ApplicationError, frame::Frame, library};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
@@ -17,36 +18,479 @@ pub struct Config {
     pub export_body_history: bool,
     #[serde(default)]
     pub export_system_parameters_history: bool,
+    #[serde(default = "SystemParameter::all")]
+    pub system_parameters_columns: Vec<SystemParameter>,
     #[serde(default)]
     pub plot_system: bool,
     #[serde(default)]
     pub plot_system_kinetic_energy: bool,
     #[serde(default)]
     pub plot_system_potential_energy: bool,
+    #[serde(default)]
+    pub export_test_particle_history: bool,
+    #[serde(default)]
+    pub threads: Option<usize>,
+    #[serde(default)]
+    pub frame: Frame,
+    #[serde(default)]
+    pub export_relative_to: Option<String>,
+    #[serde(default)]
+    pub stdout: Option<StdoutFormat>,
+    /// Integrate backwards in time
+    #[serde(default)]
+    pub reverse: bool,
+    /// Write the smallest distance every pair of bodies reached, and when, as matrices
+    #[serde(default)]
+    pub export_min_distances: bool,
+    /// Count in the summary how often two bodies came closer than this distance in m
+    #[serde(default)]
+    pub close_encounter_distance: Option<f64>,
+    /// Refuse to run a system that the stability checks find obviously unstable
+    #[serde(default)]
+    pub strict_stability: bool,
+    /// Integrate a shadow system and export the maximal Lyapunov exponent and MEGNO
+    #[serde(default)]
+    pub export_chaos_indicators: bool,
+    /// Relative perturbation of the positions and velocities of the shadow system
+    #[serde(default = "Config::default_shadow_perturbation")]
+    pub shadow_perturbation: f64,
+}
+
+impl Config {
+    pub fn default_shadow_perturbation() -> f64 {
+        1e-8
+    }
+
+    /// The timestep with its sign, negative when integrating backwards
+    pub fn signed_timestep(&self) -> f64 {
+        if self.reverse {
+            -self.timestep
+        } else {
+            self.timestep
+        }
+    }
+}
+
+/// Formats in which the export steps can be streamed to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum StdoutFormat {
+    /// One JSON object per export step and line
+    Jsonl,
+}
+
+/// The diagnostics that can be written to the system parameters csv file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemParameter {
+    /// K + U
+    Energy,
+    KineticEnergy,
+    PotentialEnergy,
+    Impulse,
+    CenterOfMass,
+    /// Angular momentum about the origin
+    AngularMomentum,
+    /// Angular momentum about the center of mass
+    BarycentricAngularMomentum,
+    /// 2K/|U|
+    VirialRatio,
+}
+
+impl SystemParameter {
+    pub fn all() -> Vec<SystemParameter> {
+        vec![
+            SystemParameter::Energy,
+            SystemParameter::KineticEnergy,
+            SystemParameter::PotentialEnergy,
+            SystemParameter::Impulse,
+            SystemParameter::CenterOfMass,
+            SystemParameter::AngularMomentum,
+            SystemParameter::BarycentricAngularMomentum,
+            SystemParameter::VirialRatio,
+        ]
+    }
+
+    /// The csv headers of this diagnostic
+    pub fn headers(&self) -> &'static [&'static str] {
+        match self {
+            SystemParameter::Energy => &["Energy"],
+            SystemParameter::KineticEnergy => &["Kinetic energy"],
+            SystemParameter::PotentialEnergy => &["Potential energy"],
+            SystemParameter::Impulse => &["Impulse x", "Impulse y"],
+            SystemParameter::CenterOfMass => &["Center of mass x", "Center of mass y"],
+            SystemParameter::AngularMomentum => &["Angular momentum"],
+            SystemParameter::BarycentricAngularMomentum => &["Barycentric angular momentum"],
+            SystemParameter::VirialRatio => &["Virial ratio"],
+        }
+    }
+}
+
+/// A massless tracer that feels the gravity of the bodies of the system
+/// but does not attract anything itself.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TestParticle {
+    pub position: Vec2d,
+    pub velocity: Vec2d,
+}
+
+/// Properties of a body that `kepler_core` does not need, given next to
+/// its mass in the config and used by the stability checks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct BodyProperties {
+    /// Radius in m
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radius: Option<f64>,
+    /// Mean density in kg/m³
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub density: Option<f64>,
+}
+
+impl BodyProperties {
+    /// The given density, or the mean density of a sphere with the radius and `mass`
+    pub fn density(&self, mass: f64) -> Option<f64> {
+        self.density.or_else(|| {
+            self.radius
+                .map(|radius| mass / (4.0 / 3.0 * std::f64::consts::PI * radius.powi(3)))
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.radius.is_none() && self.density.is_none()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "ConfigSystemFile", into = "ConfigSystemFile")]
 pub struct ConfigSystem {
     pub config: Config,
     pub system: System,
+    pub test_particles: Vec<TestParticle>,
+    /// Radius and density of the bodies that have them, by name
+    pub body_properties: BTreeMap<String, BodyProperties>,
+}
+
+/// The layout of the config file. The test particles are listed in the
+/// `[system]` table next to the bodies, but are kept out of the `System`
+/// handed to `kepler_core`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ConfigSystemFile {
+    config: Config,
+    system: SystemTable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SystemTable {
+    bodies: Vec<BodyEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    test_particles: Vec<TestParticle>,
+}
+
+/// A body as written in the config, with the properties `kepler_core` does not know
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BodyEntry {
+    name: String,
+    mass: f64,
+    position: Vec2d,
+    velocity: Vec2d,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    radius: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    density: Option<f64>,
+}
+
+impl From<ConfigSystemFile> for ConfigSystem {
+    fn from(value: ConfigSystemFile) -> Self {
+        let mut bodies = vec![];
+        let mut body_properties = BTreeMap::new();
+        for entry in value.system.bodies {
+            let properties = BodyProperties {
+                radius: entry.radius,
+                density: entry.density,
+            };
+            if !properties.is_empty() {
+                body_properties.insert(entry.name.clone(), properties);
+            }
+            bodies.push(Body {
+                name: entry.name,
+                mass: entry.mass,
+                position: entry.position,
+                velocity: entry.velocity,
+            });
+        }
+        Self {
+            config: value.config,
+            system: System { bodies },
+            test_particles: value.system.test_particles,
+            body_properties,
+        }
+    }
+}
+
+impl From<ConfigSystem> for ConfigSystemFile {
+    fn from(value: ConfigSystem) -> Self {
+        let body_properties = value.body_properties;
+        Self {
+            config: value.config,
+            system: SystemTable {
+                bodies: value
+                    .system
+                    .bodies
+                    .into_iter()
+                    .map(|body| {
+                        let properties =
+                            body_properties.get(&body.name).copied().unwrap_or_default();
+                        BodyEntry {
+                            name: body.name,
+                            mass: body.mass,
+                            position: body.position,
+                            velocity: body.velocity,
+                            radius: properties.radius,
+                            density: properties.density,
+                        }
+                    })
+                    .collect(),
+                test_particles: value.test_particles,
+            },
+        }
+    }
+}
+
+/// The file formats a config can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+    Ron,
+}
+
+impl ConfigFormat {
+    /// The format belonging to the extension of `path`, if it is a known one
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "ron" => Some(ConfigFormat::Ron),
+            _ => None,
+        }
+    }
+
+    /// The given format, or the one of the extension of `path`, falling back to toml
+    pub fn detect(format: Option<Self>, path: impl AsRef<Path>) -> Self {
+        format
+            .or_else(|| ConfigFormat::from_path(path))
+            .unwrap_or(ConfigFormat::Toml)
+    }
 }
 
 impl ConfigSystem {
+    /// Reads a config file, the format is taken from the file extension
     pub fn parse(filename: String) -> Result<Self, ApplicationError> {
-        let mut file = File::open(filename)?;
+        Self::parse_as(filename, None)
+    }
+
+    /// Reads a config file in the given format, or the one of the file extension if `None`.
+    ///
+    /// Included files are loaded and bodies taken `from` a library are resolved.
+    pub fn parse_as(
+        filename: String,
+        format: Option<ConfigFormat>,
+    ) -> Result<Self, ApplicationError> {
+        let format = ConfigFormat::detect(format, &filename);
+        let mut file = File::open(&filename)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
 
-        let config_result: Result<ConfigSystem, toml::de::Error> = toml::from_str(&contents);
-        match config_result {
-            Ok(config) => Ok(config),
-            Err(e) => {
-                tracing::event!(
-                    tracing::Level::ERROR,
-                    "Error when parsing config from file {e}"
-                );
-                Err(ApplicationError::from(e))
+        let config_result = Self::value_from_str_as(&contents, format).and_then(|value| {
+            if library::needs_resolution(&value) {
+                let value = library::resolve(Path::new(&filename), value)?;
+                check_planar(&value)?;
+                Ok(serde_json::from_value(value)?)
+            } else {
+                check_planar(&value)?;
+                // parsing the text directly keeps the positions in error messages
+                Self::from_str_as(&contents, format)
+            }
+        });
+        if let Err(e) = &config_result {
+            tracing::event!(
+                tracing::Level::ERROR,
+                "Error when parsing config from file {e}"
+            );
+        }
+        config_result
+    }
+
+    pub fn from_str_as(contents: &str, format: ConfigFormat) -> Result<Self, ApplicationError> {
+        Ok(match format {
+            ConfigFormat::Toml => toml::from_str(contents)?,
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(contents)?,
+            ConfigFormat::Ron => ron::from_str(contents)?,
+        })
+    }
+
+    /// Parses a config file without interpreting it, e.g. to resolve includes
+    pub fn value_from_str_as(
+        contents: &str,
+        format: ConfigFormat,
+    ) -> Result<serde_json::Value, ApplicationError> {
+        Ok(match format {
+            ConfigFormat::Toml => toml::from_str(contents)?,
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(contents)?,
+            // ron cannot read unnamed structs with more than one field into other
+            // self-describing types, but into its own value
+            ConfigFormat::Ron => ron::from_str::<ron::Value>(contents)?.into_rust()?,
+        })
+    }
+
+    pub fn to_string_as(&self, format: ConfigFormat) -> Result<String, ApplicationError> {
+        Ok(match format {
+            ConfigFormat::Toml => toml::to_string(self)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            ConfigFormat::Yaml => serde_yaml::to_string(self)?,
+            ConfigFormat::Ron => {
+                ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())? + "\n"
+            }
+        })
+    }
+}
+
+/// Rejects bodies and test particles that leave the plane.
+///
+/// `kepler_core` simulates in the plane, so a `z` in a position or velocity
+/// would otherwise be dropped without notice. A `z` of zero is accepted.
+pub fn check_planar(value: &serde_json::Value) -> Result<(), ApplicationError> {
+    let entries = |key: &str| {
+        value
+            .pointer(&format!("/system/{key}"))
+            .and_then(serde_json::Value::as_array)
+            .map_or(&[][..], Vec::as_slice)
+    };
+    let states = entries("bodies")
+        .iter()
+        .enumerate()
+        .map(|(index, body)| {
+            let name = body.get("name").and_then(serde_json::Value::as_str);
+            let path = match name {
+                Some(name) => format!("system.bodies[{name}]"),
+                None => format!("system.bodies[{index}]"),
+            };
+            (path, body)
+        })
+        .chain(
+            entries("test_particles")
+                .iter()
+                .enumerate()
+                .map(|(index, particle)| (format!("system.test_particles[{index}]"), particle)),
+        );
+    for (path, state) in states {
+        for vector in ["position", "velocity"] {
+            let z = state.get(vector).and_then(|vector| vector.get("z"));
+            if let Some(z) = z.filter(|z| z.as_f64() != Some(0.0)) {
+                return Err(ApplicationError::OutOfPlaneError(format!(
+                    "{path}.{vector}.z is {z}, but simulations are two-dimensional, only x and y are supported"
+                )));
             }
         }
     }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_planar, ConfigFormat, ConfigSystem};
+
+    #[test]
+    pub fn parses_test_particles_next_to_bodies() {
+        let contents = r#"
+            [config]
+            timestep = 1.0
+            steps = 10
+            export_step = 1
+            export_directory = "export_files"
+            export_file_name_prefix = "SIM"
+
+            [[system.bodies]]
+            name = "Sun"
+            mass = 1.989e30
+            position = { x = 0.0, y = 0.0 }
+            velocity = { x = 0.0, y = 0.0 }
+
+            [[system.test_particles]]
+            position = { x = 1.0e11, y = 0.0 }
+            velocity = { x = 0.0, y = 3.0e4 }
+        "#;
+        let configsystem: ConfigSystem = toml::from_str(contents).unwrap();
+        assert_eq!(configsystem.system.bodies.len(), 1);
+        assert_eq!(configsystem.test_particles.len(), 1);
+        assert_eq!(configsystem.test_particles[0].velocity.y, 3.0e4);
+    }
+
+    #[test]
+    pub fn converts_between_all_formats() {
+        let contents = r#"
+            [config]
+            timestep = 1.0
+            steps = 10
+            export_step = 1
+            export_directory = "export_files"
+            export_file_name_prefix = "SIM"
+            frame = "heliocentric:Sun"
+
+            [[system.bodies]]
+            name = "Sun"
+            mass = 1.989e30
+            position = { x = 0.0, y = 0.0 }
+            velocity = { x = 0.0, y = 0.0 }
+
+            [[system.test_particles]]
+            position = { x = 1.0e11, y = 0.0 }
+            velocity = { x = 0.0, y = 3.0e4 }
+        "#;
+        let original = ConfigSystem::from_str_as(contents, ConfigFormat::Toml).unwrap();
+        for format in [ConfigFormat::Json, ConfigFormat::Yaml, ConfigFormat::Ron] {
+            let converted = original.to_string_as(format).unwrap();
+            let parsed = ConfigSystem::from_str_as(&converted, format).unwrap();
+            assert_eq!(
+                parsed.to_string_as(ConfigFormat::Toml).unwrap(),
+                original.to_string_as(ConfigFormat::Toml).unwrap(),
+                "{format:?}"
+            );
+            let value = ConfigSystem::value_from_str_as(&converted, format).unwrap();
+            assert_eq!(value["system"]["bodies"][0]["name"], "Sun", "{format:?}");
+        }
+    }
+
+    #[test]
+    pub fn rejects_bodies_out_of_the_plane() {
+        let contents = r#"
+            [[system.bodies]]
+            name = "Sun"
+            position = { x = 0.0, y = 0.0, z = 0.0 }
+            velocity = { x = 0.0, y = 0.0 }
+
+            [[system.bodies]]
+            name = "Pluto"
+            position = { x = 5.9e12, y = 0.0 }
+            velocity = { x = 0.0, y = 4.7e3, z = 1.3e3 }
+        "#;
+        let value = ConfigSystem::value_from_str_as(contents, ConfigFormat::Toml).unwrap();
+        let error = check_planar(&value).unwrap_err().to_string();
+        assert!(
+            error.starts_with("system.bodies[Pluto].velocity.z is 1300.0"),
+            "{error}"
+        );
+
+        let value = ConfigSystem::value_from_str_as(
+            &contents.replace("z = 1.3e3", "z = 0"),
+            ConfigFormat::Toml,
+        )
+        .unwrap();
+        assert!(check_planar(&value).is_ok());
+    }
 }
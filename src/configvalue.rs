@@ -0,0 +1,325 @@
+use toml::Value;
+
+use crate::{configsystem::ConfigSystem, error::ApplicationError};
+
+/// One step of a path like `system.bodies[Earth].mass`
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    /// An array element, addressed by index or by the `name` of the element
+    Element(String),
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>, ApplicationError> {
+    let invalid = || ApplicationError::ConfigPathError(format!("invalid path `{path}`"));
+    let mut segments = vec![];
+    for part in path.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(index) => part.split_at(index),
+            None => (part, ""),
+        };
+        if key.is_empty() {
+            return Err(invalid());
+        }
+        segments.push(Segment::Key(key.to_owned()));
+        while !rest.is_empty() {
+            let end = rest.find(']').ok_or_else(invalid)?;
+            let element = &rest[1..end];
+            if element.is_empty() {
+                return Err(invalid());
+            }
+            segments.push(Segment::Element(element.to_owned()));
+            rest = &rest[end + 1..];
+            if !rest.is_empty() && !rest.starts_with('[') {
+                return Err(invalid());
+            }
+        }
+    }
+    Ok(segments)
+}
+
+fn find_element<'a>(array: &'a mut [Value], element: &str) -> Option<&'a mut Value> {
+    if let Ok(index) = element.parse::<usize>() {
+        return array.get_mut(index);
+    }
+    let name = |value: &Value| value.get("name").and_then(Value::as_str).map(str::to_owned);
+    // an exact match wins over one that only differs in case
+    let index = array
+        .iter()
+        .position(|value| name(value).as_deref() == Some(element))
+        .or_else(|| {
+            array.iter().position(|value| {
+                name(value).is_some_and(|name| name.eq_ignore_ascii_case(element))
+            })
+        })?;
+    array.get_mut(index)
+}
+
+fn lookup<'a>(
+    root: &'a mut Value,
+    segments: &[Segment],
+    path: &str,
+) -> Result<&'a mut Value, ApplicationError> {
+    let mut current = root;
+    for segment in segments {
+        let next = match segment {
+            Segment::Key(key) if !current.is_array() => current.get_mut(key.as_str()),
+            // `system.bodies.Earth` works as well as `system.bodies[Earth]`
+            Segment::Key(element) | Segment::Element(element) => current
+                .as_array_mut()
+                .and_then(|array| find_element(array, element)),
+        };
+        current = next.ok_or_else(|| {
+            ApplicationError::ConfigPathError(format!("`{path}` does not exist in the config"))
+        })?;
+    }
+    Ok(current)
+}
+
+/// The keys that are left out of a table of the config when they are not set,
+/// so they can be added by `set_value`. Any other missing key is a typo.
+fn optional_keys(parents: &[Segment]) -> &'static [&'static str] {
+    let keys: Vec<&str> = parents
+        .iter()
+        .map(|segment| match segment {
+            Segment::Key(key) | Segment::Element(key) => key.as_str(),
+        })
+        .collect();
+    match keys.as_slice() {
+        ["config"] => &[
+            "threads",
+            "export_relative_to",
+            "stdout",
+            "close_encounter_distance",
+        ],
+        ["system"] => &["test_particles"],
+        ["system", "bodies", _] => &["radius", "density"],
+        _ => &[],
+    }
+}
+
+/// Returns the value at `path`, e.g. `config.timestep` or `system.bodies[Earth].velocity.y`.
+/// Array elements are addressed by index or by their name.
+pub fn get_value(configsystem: &ConfigSystem, path: &str) -> Result<Value, ApplicationError> {
+    let segments = parse_path(path)?;
+    let mut root = Value::try_from(configsystem)?;
+    lookup(&mut root, &segments, path).cloned()
+}
+
+/// Returns a copy of the config with the value at `path` replaced.
+///
+/// Optional settings that are not in the config, like `config.threads` or the
+/// `radius` of a body, are added; any other key has to exist already.
+/// Numbers are converted to the type of the value they replace, so a float can
+/// be used for `config.steps`.
+pub fn set_value(
+    configsystem: &ConfigSystem,
+    path: &str,
+    value: Value,
+) -> Result<ConfigSystem, ApplicationError> {
+    let segments = parse_path(path)?;
+    let mut root = Value::try_from(configsystem)?;
+
+    let (last, parents) = segments
+        .split_last()
+        .expect("that a parsed path has at least one segment");
+    let parent = lookup(&mut root, parents, path)?;
+    let target = match (last, parent.as_table_mut()) {
+        (Segment::Key(key), Some(table))
+            if !table.contains_key(key) && optional_keys(parents).contains(&key.as_str()) =>
+        {
+            table.entry(key.as_str()).or_insert(value.clone())
+        }
+        _ => lookup(parent, std::slice::from_ref(last), path)?,
+    };
+    *target = match (&*target, value) {
+        (Value::Integer(_), Value::Float(float)) => Value::Integer(float.round() as i64),
+        (Value::Float(_), Value::Integer(integer)) => Value::Float(integer as f64),
+        (_, value) => value,
+    };
+
+    Ok(root.try_into()?)
+}
+
+/// Parses the value of an override as toml, anything that is not valid toml is taken as string
+pub fn parse_value(text: &str) -> Value {
+    match toml::from_str::<toml::Table>(&format!("value = {text}")) {
+        Ok(mut table) => table
+            .remove("value")
+            .unwrap_or(Value::String(text.to_owned())),
+        Err(_) => Value::String(text.to_owned()),
+    }
+}
+
+/// Splits an override like `config.timestep=200` into path and value
+pub fn parse_override(text: &str) -> Result<(String, Value), ApplicationError> {
+    let (path, value) = text.split_once('=').ok_or_else(|| {
+        ApplicationError::ConfigPathError(format!(
+            "invalid override `{text}`, expected `path=value`"
+        ))
+    })?;
+    Ok((path.trim().to_owned(), parse_value(value.trim())))
+}
+
+/// The config path of an environment variable like `KEPLER_CONFIG__TIMESTEP` or
+/// `KEPLER_SYSTEM__BODIES__EARTH__MASS`, segments are separated by two underscores.
+/// Other variables, even with the `KEPLER_` prefix, are not overrides.
+pub fn path_from_env_name(name: &str) -> Option<String> {
+    let rest = name.strip_prefix("KEPLER_")?;
+    let segments: Vec<String> = rest.split("__").map(str::to_ascii_lowercase).collect();
+    match segments.first().map(String::as_str) {
+        Some("config" | "system") if segments.iter().all(|segment| !segment.is_empty()) => {
+            Some(segments.join("."))
+        }
+        _ => None,
+    }
+}
+
+/// Applies the overrides from the environment and then the ones given with `--set`,
+/// so the command line wins
+pub fn apply_overrides_from(
+    configsystem: ConfigSystem,
+    environment: impl Iterator<Item = (String, String)>,
+    overrides: &[String],
+) -> Result<ConfigSystem, ApplicationError> {
+    let mut environment: Vec<(String, Value)> = environment
+        .filter_map(|(name, value)| Some((path_from_env_name(&name)?, parse_value(&value))))
+        .collect();
+    // the environment has no order, so make the result independent of it
+    environment.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut configsystem = configsystem;
+    for entry in environment
+        .into_iter()
+        .map(Ok)
+        .chain(overrides.iter().map(|text| parse_override(text)))
+    {
+        let (path, value) = entry?;
+        tracing::event!(tracing::Level::DEBUG, "Setting {path} to {value}");
+        configsystem = set_value(&configsystem, &path, value)?;
+    }
+    Ok(configsystem)
+}
+
+/// Applies the `KEPLER_` environment variables and the `--set` overrides
+pub fn apply_overrides(
+    configsystem: ConfigSystem,
+    overrides: &[String],
+) -> Result<ConfigSystem, ApplicationError> {
+    apply_overrides_from(configsystem, std::env::vars(), overrides)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply_overrides_from, get_value, path_from_env_name, set_value};
+    use crate::configsystem::ConfigSystem;
+    use toml::Value;
+
+    fn configsystem() -> ConfigSystem {
+        toml::from_str(
+            r#"
+            [config]
+            timestep = 1.0
+            steps = 10
+            export_step = 1
+            export_directory = "export_files"
+            export_file_name_prefix = "SIM"
+
+            [[system.bodies]]
+            name = "Sun"
+            mass = 1.989e30
+            position = { x = 0.0, y = 0.0 }
+            velocity = { x = 0.0, y = 0.0 }
+
+            [[system.bodies]]
+            name = "Earth"
+            mass = 5.972e24
+            position = { x = 1.5e11, y = 0.0 }
+            velocity = { x = 0.0, y = 3.0e4 }
+        "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    pub fn sets_values_by_body_name_and_index() {
+        let configsystem = configsystem();
+        let configsystem = set_value(
+            &configsystem,
+            "system.bodies[Earth].mass",
+            Value::Float(6e24),
+        )
+        .unwrap();
+        let configsystem = set_value(
+            &configsystem,
+            "system.bodies[0].velocity.x",
+            Value::Float(1.0),
+        )
+        .unwrap();
+        let configsystem = set_value(&configsystem, "config.steps", Value::Float(20.4)).unwrap();
+        let configsystem = set_value(&configsystem, "config.threads", Value::Integer(2)).unwrap();
+        let configsystem = set_value(
+            &configsystem,
+            "system.bodies.earth.radius",
+            Value::Float(6.4e6),
+        )
+        .unwrap();
+
+        assert_eq!(configsystem.system.bodies[1].mass, 6e24);
+        assert_eq!(configsystem.system.bodies[0].velocity.x, 1.0);
+        assert_eq!(configsystem.config.steps, 20);
+        assert_eq!(configsystem.config.threads, Some(2));
+        assert_eq!(configsystem.body_properties["Earth"].radius, Some(6.4e6));
+        assert_eq!(
+            get_value(&configsystem, "config.timestep").unwrap(),
+            Value::Float(1.0)
+        );
+    }
+
+    #[test]
+    pub fn rejects_unknown_paths() {
+        let configsystem = configsystem();
+        assert!(set_value(&configsystem, "system.bodies[Mars].mass", Value::Float(1.0)).is_err());
+        assert!(set_value(&configsystem, "config.timestep.x", Value::Float(1.0)).is_err());
+        assert!(set_value(&configsystem, "config.timestpe", Value::Float(1.0)).is_err());
+        assert!(set_value(&configsystem, "system.bodies[Earth].mas", Value::Float(1.0)).is_err());
+        assert!(set_value(&configsystem, "config.radius", Value::Float(1.0)).is_err());
+        assert!(get_value(&configsystem, "config..timestep").is_err());
+    }
+
+    #[test]
+    pub fn applies_environment_before_command_line_overrides() {
+        assert_eq!(
+            path_from_env_name("KEPLER_SYSTEM__BODIES__EARTH__MASS").as_deref(),
+            Some("system.bodies.earth.mass")
+        );
+        assert_eq!(path_from_env_name("KEPLER_HOME"), None);
+
+        let environment = [
+            ("KEPLER_CONFIG__STEPS".to_owned(), "50".to_owned()),
+            ("KEPLER_CONFIG__TIMESTEP".to_owned(), "5".to_owned()),
+            (
+                "KEPLER_SYSTEM__BODIES__EARTH__MASS".to_owned(),
+                "6e24".to_owned(),
+            ),
+            ("PATH".to_owned(), "/usr/bin".to_owned()),
+        ];
+        let overrides = [
+            "config.timestep=200".to_owned(),
+            "config.export_directory=out".to_owned(),
+        ];
+        let configsystem =
+            apply_overrides_from(configsystem(), environment.into_iter(), &overrides).unwrap();
+
+        assert_eq!(configsystem.config.steps, 50);
+        assert_eq!(configsystem.config.timestep, 200.0);
+        assert_eq!(configsystem.config.export_directory, "out");
+        assert_eq!(configsystem.system.bodies[1].mass, 6e24);
+        assert!(apply_overrides_from(
+            configsystem,
+            std::iter::empty(),
+            &["config.timestep".to_owned()]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use kepler_core::types::System;
+use maths_rs::Vec2d;
+use serde::Serialize;
+
+use crate::{
+    configsystem::{Config, TestParticle},
+    error::ApplicationError,
+    frame::{apply_frame, Frame},
+    simulation::Simulation,
+};
+
+/// The result of one run of a convergence study
+#[derive(Debug, Clone, Serialize)]
+pub struct ConvergenceRun {
+    pub timestep: f64,
+    pub steps: i64,
+    /// Largest |E - E₀| / |E₀| at the export steps of the config
+    pub max_relative_energy_error: f64,
+    /// Largest distance of a body from its final position in the previous run
+    pub position_difference: Option<f64>,
+    /// Largest difference of a body's final velocity to the previous run
+    pub velocity_difference: Option<f64>,
+    /// Order estimated from the position differences to the two previous runs
+    pub position_order: Option<f64>,
+    /// Order estimated from the energy error of the previous run
+    pub energy_order: Option<f64>,
+}
+
+/// Runs of the same config at halved timesteps
+#[derive(Debug, Clone, Serialize)]
+pub struct ConvergenceStudy {
+    pub simulated_time: f64,
+    /// Largest initial distance of a body from the origin, in the frame of the config
+    pub extent: f64,
+    pub runs: Vec<ConvergenceRun>,
+}
+
+fn length(vector: Vec2d) -> f64 {
+    (vector.x * vector.x + vector.y * vector.y).sqrt()
+}
+
+/// The order `p` for errors that shrink as `timestepᵖ` when the timestep is halved
+fn order(coarse_error: f64, fine_error: f64) -> Option<f64> {
+    let order = (coarse_error / fine_error).log2();
+    order.is_finite().then_some(order)
+}
+
+/// Runs the config with the timestep divided by `refinement` for the same simulated
+/// time, and returns the final system and the largest relative energy error
+fn run_refined(
+    config: &Config,
+    system: System,
+    test_particles: Vec<TestParticle>,
+    refinement: i64,
+) -> Result<(System, f64), ApplicationError> {
+    let config = Config {
+        timestep: config.timestep / refinement as f64,
+        steps: config.steps * refinement,
+        export_step: config.export_step * refinement,
+        // the initial state is already in the frame of the config
+        frame: Frame::AsGiven,
+        ..config.clone()
+    };
+    let (system, summary) = Simulation::builder(config, system)
+        .test_particles(test_particles)
+        .exports(false)
+        .build()?
+        .run()?;
+    Ok((system, summary.max_relative_energy_error))
+}
+
+/// Runs the config `runs` times, starting at its timestep and halving it for every
+/// run, and compares the final states of the bodies of consecutive runs.
+///
+/// Nothing is exported.
+pub fn study_convergence(
+    config: &Config,
+    mut system: System,
+    mut test_particles: Vec<TestParticle>,
+    runs: u32,
+) -> Result<ConvergenceStudy, ApplicationError> {
+    apply_frame(&config.frame, &mut system, &mut test_particles)?;
+    let extent = system
+        .bodies
+        .iter()
+        .map(|body| length(body.position))
+        .fold(0.0, f64::max);
+
+    let mut results: Vec<ConvergenceRun> = vec![];
+    let mut previous: Option<System> = None;
+    for run in 0..runs {
+        let refinement = 1 << run;
+        let (last, energy_error) =
+            run_refined(config, system.clone(), test_particles.clone(), refinement)?;
+        let differences = previous.as_ref().map(|previous| {
+            previous
+                .bodies
+                .iter()
+                .zip(last.bodies.iter())
+                .map(|(previous, body)| {
+                    (
+                        length(body.position - previous.position),
+                        length(body.velocity - previous.velocity),
+                    )
+                })
+                .fold((0.0, 0.0), |(position, velocity): (f64, f64), (p, v)| {
+                    (position.max(p), velocity.max(v))
+                })
+        });
+        let position_difference = differences.map(|(position, _)| position);
+        let position_order = results
+            .last()
+            .and_then(|previous| previous.position_difference)
+            .zip(position_difference)
+            .and_then(|(coarse, fine)| order(coarse, fine));
+        let energy_order = results
+            .last()
+            .and_then(|previous| order(previous.max_relative_energy_error, energy_error));
+        tracing::event!(
+            tracing::Level::INFO,
+            "Finished run {} of {runs} with {} steps",
+            run + 1,
+            config.steps * refinement
+        );
+        results.push(ConvergenceRun {
+            timestep: config.timestep / refinement as f64,
+            steps: config.steps * refinement,
+            max_relative_energy_error: energy_error,
+            position_difference,
+            velocity_difference: differences.map(|(_, velocity)| velocity),
+            position_order,
+            energy_order,
+        });
+        previous = Some(last);
+    }
+
+    Ok(ConvergenceStudy {
+        simulated_time: config.steps as f64 * config.signed_timestep(),
+        extent,
+        runs: results,
+    })
+}
+
+impl ConvergenceStudy {
+    /// The order estimated from the position differences of the two finest runs
+    pub fn position_order(&self) -> Option<f64> {
+        self.runs.last().and_then(|run| run.position_order)
+    }
+
+    /// The order estimated from the energy errors of the two finest runs
+    pub fn energy_order(&self) -> Option<f64> {
+        self.runs.last().and_then(|run| run.energy_order)
+    }
+}
+
+impl std::fmt::Display for ConvergenceStudy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let optional =
+            |value: Option<f64>, format: fn(f64) -> String| value.map_or("-".to_owned(), format);
+        writeln!(
+            f,
+            "{:>12}  {:>9}  {:>14}  {:>6}  {:>14}  {:>14}  {:>6}",
+            "Timestep/s",
+            "Steps",
+            "Energy error",
+            "Order",
+            "Δ position/m",
+            "Δ velocity/m/s",
+            "Order"
+        )?;
+        for run in self.runs.iter() {
+            writeln!(
+                f,
+                "{:>12.5e}  {:>9}  {:>14.6e}  {:>6}  {:>14}  {:>14}  {:>6}",
+                run.timestep,
+                run.steps,
+                run.max_relative_energy_error,
+                optional(run.energy_order, |order| format!("{order:.2}")),
+                optional(run.position_difference, |difference| format!(
+                    "{difference:.6e}"
+                )),
+                optional(run.velocity_difference, |difference| format!(
+                    "{difference:.6e}"
+                )),
+                optional(run.position_order, |order| format!("{order:.2}")),
+            )?;
+        }
+        writeln!(
+            f,
+            "Empirical order: {} from the positions, {} from the energy error",
+            optional(self.position_order(), |order| format!("{order:.2}")),
+            optional(self.energy_order(), |order| format!("{order:.2}")),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::study_convergence;
+    use crate::{
+        configsystem::Config,
+        generate::{circular_speed, ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS},
+    };
+    use kepler_core::types::{Body, System};
+    use maths_rs::Vec2d;
+
+    #[test]
+    pub fn estimates_first_order_for_semi_implicit_euler() {
+        let contents = r#"
+            timestep = 86400.0
+            steps = 100
+            export_step = 10
+            export_directory = "export_files"
+            export_file_name_prefix = "SIM"
+        "#;
+        let config: Config = toml::from_str(contents).unwrap();
+        let system = System {
+            bodies: vec![
+                Body {
+                    name: "Sun".to_owned(),
+                    mass: SUN_MASS,
+                    position: Vec2d::new(0.0, 0.0),
+                    velocity: Vec2d::new(0.0, 0.0),
+                },
+                Body {
+                    name: "Earth".to_owned(),
+                    mass: EARTH_MASS,
+                    position: Vec2d::new(ASTRONOMICAL_UNIT, 0.0),
+                    velocity: Vec2d::new(0.0, 0.9 * circular_speed(SUN_MASS, ASTRONOMICAL_UNIT)),
+                },
+            ],
+        };
+
+        let study = study_convergence(&config, system, vec![], 4).unwrap();
+        assert_eq!(study.runs.len(), 4);
+        assert_eq!(study.runs[3].steps, 800);
+        assert!(study.runs[0].position_difference.is_none());
+        let order = study.position_order().unwrap();
+        assert!((order - 1.0).abs() < 0.2, "{study}");
+        let energy_order = study.energy_order().unwrap();
+        assert!((energy_order - 1.0).abs() < 0.2, "{study}");
+    }
+}
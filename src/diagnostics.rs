@@ -0,0 +1,184 @@
+use kepler_core::{
+    center_of_mass::calculate_center_of_mass, energy::calculate_kinetic_energy,
+    impulse::calculate_total_impulse, types::System,
+};
+use maths_rs::Vec2d;
+
+use crate::{configsystem::SystemParameter, gravity::GRAVITATIONAL_CONSTANT};
+
+pub fn calculate_total_kinetic_energy(system: &System) -> f64 {
+    system.bodies.iter().map(calculate_kinetic_energy).sum()
+}
+
+/// The gravitational potential energy -Σ G mᵢ mⱼ / rᵢⱼ, every pair counted once.
+///
+/// `kepler_core` counts every pair twice in its system energy.
+pub fn calculate_total_potential_energy(system: &System) -> f64 {
+    system
+        .bodies
+        .iter()
+        .enumerate()
+        .map(|(i, body)| {
+            system
+                .bodies
+                .iter()
+                .skip(i + 1)
+                .map(|other| {
+                    let distance = other.position - body.position;
+                    -GRAVITATIONAL_CONSTANT * body.mass * other.mass
+                        / (distance.x * distance.x + distance.y * distance.y).sqrt()
+                })
+                .sum::<f64>()
+        })
+        .sum()
+}
+
+/// The total energy K + U, which is conserved, unlike the system energy of
+/// `kepler_core` that counts the potential of every pair twice
+pub fn calculate_total_energy(system: &System) -> f64 {
+    calculate_total_kinetic_energy(system) + calculate_total_potential_energy(system)
+}
+
+/// The velocity of the center of mass
+pub fn calculate_barycentric_velocity(system: &System) -> Vec2d {
+    let total_mass: f64 = system.bodies.iter().map(|body| body.mass).sum();
+    calculate_total_impulse(system) * (1.0 / total_mass)
+}
+
+/// The z component of the total angular momentum about `origin`, measured
+/// in a frame moving with `frame_velocity`.
+pub fn calculate_angular_momentum(system: &System, origin: Vec2d, frame_velocity: Vec2d) -> f64 {
+    system
+        .bodies
+        .iter()
+        .map(|body| {
+            let r = body.position - origin;
+            let v = body.velocity - frame_velocity;
+            body.mass * (r.x * v.y - r.y * v.x)
+        })
+        .sum()
+}
+
+/// The total angular momentum about the center of mass, in the frame of the center of mass
+pub fn calculate_barycentric_angular_momentum(system: &System) -> f64 {
+    calculate_angular_momentum(
+        system,
+        calculate_center_of_mass(system),
+        calculate_barycentric_velocity(system),
+    )
+}
+
+/// 2K/|U|, which averages to 1 over time for a bound system in equilibrium
+pub fn calculate_virial_ratio(system: &System) -> f64 {
+    2.0 * calculate_total_kinetic_energy(system) / calculate_total_potential_energy(system).abs()
+}
+
+/// The values of one diagnostic, one for scalars and two for vectors
+pub fn calculate_system_parameter(parameter: &SystemParameter, system: &System) -> Vec<f64> {
+    match parameter {
+        SystemParameter::Energy => vec![calculate_total_energy(system)],
+        SystemParameter::KineticEnergy => vec![calculate_total_kinetic_energy(system)],
+        SystemParameter::PotentialEnergy => vec![calculate_total_potential_energy(system)],
+        SystemParameter::Impulse => {
+            let total_impulse = calculate_total_impulse(system);
+            vec![total_impulse.x, total_impulse.y]
+        }
+        SystemParameter::CenterOfMass => {
+            let center_of_mass = calculate_center_of_mass(system);
+            vec![center_of_mass.x, center_of_mass.y]
+        }
+        SystemParameter::AngularMomentum => {
+            let origin = Vec2d::new(0.0, 0.0);
+            vec![calculate_angular_momentum(system, origin, origin)]
+        }
+        SystemParameter::BarycentricAngularMomentum => {
+            vec![calculate_barycentric_angular_momentum(system)]
+        }
+        SystemParameter::VirialRatio => vec![calculate_virial_ratio(system)],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        calculate_angular_momentum, calculate_barycentric_angular_momentum,
+        calculate_system_parameter, calculate_virial_ratio,
+    };
+    use crate::configsystem::SystemParameter;
+    use crate::generate::{circular_speed, ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS};
+    use kepler_core::types::{Body, System};
+    use maths_rs::Vec2d;
+
+    #[test]
+    pub fn angular_momentum_depends_on_the_reference_point() {
+        let system = System {
+            bodies: vec![
+                Body {
+                    name: "A".to_owned(),
+                    mass: 2.0,
+                    position: Vec2d::new(1.0, 0.0),
+                    velocity: Vec2d::new(0.0, 1.0),
+                },
+                Body {
+                    name: "B".to_owned(),
+                    mass: 2.0,
+                    position: Vec2d::new(3.0, 0.0),
+                    velocity: Vec2d::new(0.0, 1.0),
+                },
+            ],
+        };
+        let origin = Vec2d::new(0.0, 0.0);
+        // about the origin both bodies contribute m * x * vy
+        assert_eq!(calculate_angular_momentum(&system, origin, origin), 8.0);
+        // the bodies move in parallel, so they do not rotate about their barycenter
+        assert_eq!(calculate_barycentric_angular_momentum(&system), 0.0);
+    }
+
+    #[test]
+    pub fn circular_orbit_is_virialised() {
+        let (sun, earth) = (SUN_MASS, 1000.0 * EARTH_MASS);
+        let total_mass = sun + earth;
+        let speed = circular_speed(total_mass, ASTRONOMICAL_UNIT);
+        // both bodies circle their barycenter
+        let body = |name: &str, mass: f64, other_mass: f64, sign: f64| Body {
+            name: name.to_owned(),
+            mass,
+            position: Vec2d::new(sign * ASTRONOMICAL_UNIT * other_mass / total_mass, 0.0),
+            velocity: Vec2d::new(0.0, sign * speed * other_mass / total_mass),
+        };
+        let system = System {
+            bodies: vec![
+                body("Sun", sun, earth, -1.0),
+                body("Earth", earth, sun, 1.0),
+            ],
+        };
+        let virial_ratio = calculate_virial_ratio(&system);
+        assert!((virial_ratio - 1.0).abs() < 1e-9, "{virial_ratio}");
+    }
+
+    #[test]
+    pub fn energy_is_the_sum_of_kinetic_and_potential_energy() {
+        let body = |name: &str, mass: f64, x: f64, vy: f64| Body {
+            name: name.to_owned(),
+            mass,
+            position: Vec2d::new(x, 0.0),
+            velocity: Vec2d::new(0.0, vy),
+        };
+        let system = System {
+            bodies: vec![
+                body("A", 1e24, 0.0, 0.0),
+                body("B", 2e22, 1e8, 500.0),
+                body("C", 3e20, -4e8, -200.0),
+            ],
+        };
+        let [energy, kinetic_energy, potential_energy] = [
+            SystemParameter::Energy,
+            SystemParameter::KineticEnergy,
+            SystemParameter::PotentialEnergy,
+        ]
+        .map(|parameter| calculate_system_parameter(&parameter, &system)[0]);
+        assert!(potential_energy < 0.0);
+        let sum = kinetic_energy + potential_energy;
+        assert!((energy - sum).abs() <= 1e-12 * sum.abs(), "{energy} {sum}");
+    }
+}
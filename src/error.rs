@@ -1,7 +1,23 @@
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum ApplicationError {
     IoError(std::io::Error),
     TomlError(toml::de::Error),
+    TomlSerializationError(toml::ser::Error),
+    CsvError(csv::Error),
+    JsonError(serde_json::Error),
+    YamlError(serde_yaml::Error),
+    RonError(ron::error::SpannedError),
+    RonSerializationError(ron::Error),
+    ConfigPathError(String),
+    IncludeError(String),
+    PlotError(plotters::drawing::DrawingAreaErrorKind<std::io::Error>),
+    InvalidArgumentError(String),
+    OutOfPlaneError(String),
+    TrajectoryError(String),
+    UnknownBodyError(String),
+    UnstableSystemError(String),
+    WebSocketError(String),
 }
 
 impl std::fmt::Display for ApplicationError {
@@ -9,10 +25,27 @@ impl std::fmt::Display for ApplicationError {
         match self {
             ApplicationError::IoError(e) => write!(f, "{e}"),
             ApplicationError::TomlError(e) => write!(f, "{e}"),
+            ApplicationError::TomlSerializationError(e) => write!(f, "{e}"),
+            ApplicationError::CsvError(e) => write!(f, "{e}"),
+            ApplicationError::JsonError(e) => write!(f, "{e}"),
+            ApplicationError::YamlError(e) => write!(f, "{e}"),
+            ApplicationError::RonError(e) => write!(f, "{e}"),
+            ApplicationError::RonSerializationError(e) => write!(f, "{e}"),
+            ApplicationError::ConfigPathError(e) => write!(f, "{e}"),
+            ApplicationError::IncludeError(e) => write!(f, "{e}"),
+            ApplicationError::PlotError(e) => write!(f, "{e}"),
+            ApplicationError::InvalidArgumentError(e) => write!(f, "{e}"),
+            ApplicationError::OutOfPlaneError(e) => write!(f, "{e}"),
+            ApplicationError::TrajectoryError(e) => write!(f, "{e}"),
+            ApplicationError::UnknownBodyError(name) => write!(f, "There is no body named {name}"),
+            ApplicationError::UnstableSystemError(e) => write!(f, "The system is unstable: {e}"),
+            ApplicationError::WebSocketError(e) => write!(f, "{e}"),
         }
     }
 }
 
+impl std::error::Error for ApplicationError {}
+
 impl From<std::io::Error> for ApplicationError {
     fn from(value: std::io::Error) -> Self {
         Self::IoError(value)
@@ -24,3 +57,54 @@ impl From<toml::de::Error> for ApplicationError {
         Self::TomlError(value)
     }
 }
+
+impl From<toml::ser::Error> for ApplicationError {
+    fn from(value: toml::ser::Error) -> Self {
+        Self::TomlSerializationError(value)
+    }
+}
+
+impl From<csv::Error> for ApplicationError {
+    fn from(value: csv::Error) -> Self {
+        Self::CsvError(value)
+    }
+}
+
+impl From<serde_json::Error> for ApplicationError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::JsonError(value)
+    }
+}
+
+impl From<serde_yaml::Error> for ApplicationError {
+    fn from(value: serde_yaml::Error) -> Self {
+        Self::YamlError(value)
+    }
+}
+
+impl From<ron::error::SpannedError> for ApplicationError {
+    fn from(value: ron::error::SpannedError) -> Self {
+        Self::RonError(value)
+    }
+}
+
+impl From<ron::Error> for ApplicationError {
+    fn from(value: ron::Error) -> Self {
+        Self::RonSerializationError(value)
+    }
+}
+
+impl From<plotters::drawing::DrawingAreaErrorKind<std::io::Error>> for ApplicationError {
+    fn from(value: plotters::drawing::DrawingAreaErrorKind<std::io::Error>) -> Self {
+        Self::PlotError(value)
+    }
+}
+
+impl From<tungstenite::Error> for ApplicationError {
+    fn from(value: tungstenite::Error) -> Self {
+        match value {
+            tungstenite::Error::Io(e) => Self::IoError(e),
+            e => Self::WebSocketError(e.to_string()),
+        }
+    }
+}
@@ -1,22 +1,26 @@
-use kepler_core::{
-    center_of_mass::calculate_center_of_mass, energy::calculate_system_energy,
-    impulse::calculate_total_impulse, types::System,
-};
+use kepler_core::types::System;
+use serde::Serialize;
 use std::{
-    error::Error,
+    collections::BTreeMap,
     fs::{DirBuilder, OpenOptions},
     io::Write,
     path::{Path, PathBuf},
 };
 
-use crate::configsystem::Config;
+use crate::{
+    chaos::ChaosIndicators,
+    configsystem::{Config, SystemParameter, TestParticle},
+    diagnostics::calculate_system_parameter,
+    error::ApplicationError,
+    summary::{MinDistances, RunSummary},
+};
 
 pub fn export_system_snapshot_to_csv(
     config: &Config,
     system: &System,
     step: i64,
     time: f64,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<(), ApplicationError> {
     let headers: Vec<String> = vec!["Time", "Name", "Mass", "x", "y", "vx", "vy"]
         .into_iter()
         .map(|s| s.to_owned())
@@ -58,7 +62,7 @@ pub fn export_system_to_csv_by_body(
     system: &System,
     step: i64,
     time: f64,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<(), ApplicationError> {
     let headers: Vec<String> = vec!["Step", "Time", "Mass", "x", "y", "vx", "vy"]
         .into_iter()
         .map(|s| s.to_owned())
@@ -78,38 +82,12 @@ pub fn export_system_to_csv_by_body(
         let filename_path = Path::new(&filename);
         let fullpath = path.join(filename_path);
 
-        // first, check if the object we want to write to exists, and if it does, if it is a file
-        match std::fs::metadata(&fullpath) {
-            Ok(metadata) => {
-                if metadata.is_file() {
-                    if step == 0 {
-                        // overwrite the file with fresh headers
-                        write_csv_headers(&fullpath, &headers)?;
-                    }
-                    // nothing to do, we can go ahead
-                } else {
-                    // we would like to write to something that exists, but it's not a file
-                    // so we return a file not found error
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::NotFound,
-                        format!(
-                            "The destination file object already exists, but it is not a file: {}",
-                            fullpath
-                                .to_str()
-                                .expect("that the path can be formatted as str")
-                        ),
-                    )));
-                }
-            }
-            Err(_e) => {
-                // if the fs object we want to write to does not exist, we create a file and write the csv headers
-                write_csv_headers(&fullpath, &headers)?;
-            }
-        }
+        prepare_csv_file(&fullpath, &headers, step)?;
 
         // in any case, we write a new line to the export file, possibly after creating it first
         let mut wtr = csv::Writer::from_writer(vec![]);
         wtr.serialize((
+            step,
             time,
             body.mass,
             body.position.x,
@@ -119,7 +97,7 @@ pub fn export_system_to_csv_by_body(
         ))?;
 
         // here we remove the generated newline character from the csv library so that we can use writeln below.
-        let text = String::from_utf8(wtr.into_inner()?)?.replace('\n', "");
+        let text = into_line(wtr)?;
 
         let mut file = OpenOptions::new().append(true).open(&fullpath)?;
         writeln!(file, "{}", text)?;
@@ -133,19 +111,15 @@ pub fn export_system_parameters_to_csv(
     system: &System,
     step: i64,
     time: f64,
-) -> Result<(), Box<dyn Error>> {
-    let headers: Vec<String> = vec![
-        "Step",
-        "Time",
-        "Energy",
-        "Impulse x",
-        "Impulse y",
-        "Center of mass x",
-        "Center of mass y",
-    ]
-    .into_iter()
-    .map(|s| s.to_owned())
-    .collect();
+) -> Result<(), ApplicationError> {
+    let mut headers: Vec<String> = vec!["Step".to_owned(), "Time".to_owned()];
+    headers.extend(
+        config
+            .system_parameters_columns
+            .iter()
+            .flat_map(|parameter| parameter.headers())
+            .map(|s| (*s).to_owned()),
+    );
 
     let path = Path::new(&config.export_directory);
 
@@ -160,19 +134,298 @@ pub fn export_system_parameters_to_csv(
     let filename_path = Path::new(&filename);
     let fullpath = path.join(filename_path);
 
+    prepare_csv_file(&fullpath, &headers, step)?;
+
+    // in any case, we write a new line to the export file, possibly after creating it first
+    let mut wtr = csv::Writer::from_writer(vec![]);
+
+    let values: Vec<f64> = config
+        .system_parameters_columns
+        .iter()
+        .flat_map(|parameter| calculate_system_parameter(parameter, system))
+        .collect();
+    wtr.serialize((step, time, values))?;
+
+    // here we remove the generated newline character from the csv library so that we can use writeln below.
+    let text = into_line(wtr)?;
+
+    let mut file = OpenOptions::new().append(true).open(&fullpath)?;
+    writeln!(file, "{}", text)?;
+
+    Ok(())
+}
+
+/// Appends the state of all test particles to one combined csv file,
+/// one row per particle and export step.
+pub fn export_test_particles_to_csv(
+    config: &Config,
+    test_particles: &[TestParticle],
+    step: i64,
+    time: f64,
+) -> Result<(), ApplicationError> {
+    let headers: Vec<String> = vec!["Step", "Time", "Particle", "x", "y", "vx", "vy"]
+        .into_iter()
+        .map(|s| s.to_owned())
+        .collect();
+
+    let path = Path::new(&config.export_directory);
+
+    if !path.exists() {
+        DirBuilder::new()
+            .recursive(true)
+            .create(path)
+            .expect("That the export path could be created.");
+    }
+
+    let filename = format! {"{}_test_particles.csv", config.export_file_name_prefix};
+    let filename_path = Path::new(&filename);
+    let fullpath = path.join(filename_path);
+
+    prepare_csv_file(&fullpath, &headers, step)?;
+
+    let file = OpenOptions::new().append(true).open(&fullpath)?;
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(file);
+    for (index, particle) in test_particles.iter().enumerate() {
+        wtr.serialize((
+            step,
+            time,
+            index,
+            particle.position.x,
+            particle.position.y,
+            particle.velocity.x,
+            particle.velocity.y,
+        ))?;
+    }
+    wtr.flush()?;
+
+    Ok(())
+}
+
+/// Appends the chaos indicators of the shadow system to `<prefix>_chaos.csv`
+pub fn export_chaos_indicators_to_csv(
+    config: &Config,
+    indicators: &ChaosIndicators,
+    step: i64,
+    time: f64,
+) -> Result<(), ApplicationError> {
+    let headers: Vec<String> = vec!["Step", "Time", "Lyapunov exponent", "MEGNO"]
+        .into_iter()
+        .map(|s| s.to_owned())
+        .collect();
+
+    let path = Path::new(&config.export_directory);
+
+    if !path.exists() {
+        DirBuilder::new()
+            .recursive(true)
+            .create(path)
+            .expect("That the export path could be created.");
+    }
+
+    let filename = format! {"{}_chaos.csv", config.export_file_name_prefix};
+    let filename_path = Path::new(&filename);
+    let fullpath = path.join(filename_path);
+
+    prepare_csv_file(&fullpath, &headers, step)?;
+
+    let file = OpenOptions::new().append(true).open(&fullpath)?;
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(file);
+    wtr.serialize((step, time, indicators.lyapunov_exponent, indicators.megno))?;
+    wtr.flush()?;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct BodyState<'a> {
+    name: &'a str,
+    mass: f64,
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+}
+
+#[derive(Serialize)]
+struct ParticleState {
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+}
+
+/// One line of the JSON Lines stream
+#[derive(Serialize)]
+struct StateRecord<'a> {
+    step: i64,
+    time: f64,
+    bodies: Vec<BodyState<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    test_particles: Vec<ParticleState>,
+    diagnostics: BTreeMap<SystemParameter, serde_json::Value>,
+}
+
+/// Writes the states of the bodies and test particles and the selected diagnostics
+/// of `system` as a single line of JSON, so the simulation can be piped into other tools.
+///
+/// The states are taken from `exported_system`, which may be relative to one of the bodies.
+pub fn export_state_to_jsonl(
+    config: &Config,
+    system: &System,
+    exported_system: &System,
+    test_particles: &[TestParticle],
+    step: i64,
+    time: f64,
+    writer: &mut impl Write,
+) -> Result<(), ApplicationError> {
+    let record = StateRecord {
+        step,
+        time,
+        bodies: exported_system
+            .bodies
+            .iter()
+            .map(|body| BodyState {
+                name: &body.name,
+                mass: body.mass,
+                x: body.position.x,
+                y: body.position.y,
+                vx: body.velocity.x,
+                vy: body.velocity.y,
+            })
+            .collect(),
+        test_particles: test_particles
+            .iter()
+            .map(|particle| ParticleState {
+                x: particle.position.x,
+                y: particle.position.y,
+                vx: particle.velocity.x,
+                vy: particle.velocity.y,
+            })
+            .collect(),
+        diagnostics: config
+            .system_parameters_columns
+            .iter()
+            .map(|parameter| {
+                let values = calculate_system_parameter(parameter, system);
+                let value = match values.as_slice() {
+                    [scalar] => serde_json::json!(scalar),
+                    vector => serde_json::json!(vector),
+                };
+                (*parameter, value)
+            })
+            .collect(),
+    };
+    serde_json::to_writer(&mut *writer, &record)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn export_summary_to_json(
+    config: &Config,
+    summary: &RunSummary,
+) -> Result<(), ApplicationError> {
+    let path = Path::new(&config.export_directory);
+
+    if !path.exists() {
+        DirBuilder::new()
+            .recursive(true)
+            .create(path)
+            .expect("That the export path could be created.");
+    }
+
+    let filename = format! {"{}_summary.json", config.export_file_name_prefix};
+    let filename_path = Path::new(&filename);
+    let fullpath = path.join(filename_path);
+
+    let file = std::fs::File::create(fullpath)?;
+    serde_json::to_writer_pretty(file, summary)?;
+
+    Ok(())
+}
+
+/// Writes the smallest distance of every pair of bodies to `<prefix>_min_distances.csv`
+/// and the time it was reached to `<prefix>_min_distance_times.csv`, as matrices
+/// with a row and a column per body.
+pub fn export_min_distances_to_csv(
+    config: &Config,
+    min_distances: &MinDistances,
+) -> Result<(), ApplicationError> {
+    let path = Path::new(&config.export_directory);
+
+    if !path.exists() {
+        DirBuilder::new()
+            .recursive(true)
+            .create(path)
+            .expect("That the export path could be created.");
+    }
+
+    let prefix = &config.export_file_name_prefix;
+    write_matrix_csv(
+        &path.join(format!("{prefix}_min_distances.csv")),
+        min_distances,
+        |(distance, _)| distance,
+    )?;
+    write_matrix_csv(
+        &path.join(format!("{prefix}_min_distance_times.csv")),
+        min_distances,
+        |(_, time)| time,
+    )?;
+
+    Ok(())
+}
+
+/// Writes one value of every pair of bodies with a row and a column per body,
+/// the diagonal is left empty
+fn write_matrix_csv(
+    fullpath: &Path,
+    min_distances: &MinDistances,
+    value: fn((f64, f64)) -> f64,
+) -> Result<(), ApplicationError> {
+    let mut wtr = csv::Writer::from_path(fullpath)?;
+
+    let mut headers = vec!["Body".to_owned()];
+    headers.extend(min_distances.names.iter().cloned());
+    wtr.write_record(&headers)?;
+    for (i, name) in min_distances.names.iter().enumerate() {
+        let mut record = vec![name.clone()];
+        record.extend((0..min_distances.names.len()).map(|j| {
+            min_distances
+                .get(i, j)
+                .map_or(String::new(), |pair| value(pair).to_string())
+        }));
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+
+    Ok(())
+}
+
+/// Makes sure that `fullpath` is a csv file we can append rows to.
+/// The file is created with headers if it does not exist yet, and overwritten
+/// with fresh headers at step 0.
+fn prepare_csv_file(
+    fullpath: &PathBuf,
+    headers: &Vec<String>,
+    step: i64,
+) -> Result<(), ApplicationError> {
     // first, check if the object we want to write to exists, and if it does, if it is a file
-    match std::fs::metadata(&fullpath) {
+    match std::fs::metadata(fullpath) {
         Ok(metadata) => {
             if metadata.is_file() {
                 if step == 0 {
                     // overwrite the file with fresh headers
-                    write_csv_headers(&fullpath, &headers)?;
+                    write_csv_headers(fullpath, headers)?;
                 }
                 // nothing to do, we can go ahead
             } else {
                 // we would like to write to something that exists, but it's not a file
                 // so we return a file not found error
-                return Err(Box::new(std::io::Error::new(
+                return Err(ApplicationError::IoError(std::io::Error::new(
                     std::io::ErrorKind::NotFound,
                     format!(
                         "The destination file object already exists, but it is not a file: {}",
@@ -185,35 +438,19 @@ pub fn export_system_parameters_to_csv(
         }
         Err(_e) => {
             // if the fs object we want to write to does not exist, we create a file and write the csv headers
-            write_csv_headers(&fullpath, &headers)?;
+            write_csv_headers(fullpath, headers)?;
         }
     }
-
-    // in any case, we write a new line to the export file, possibly after creating it first
-    let mut wtr = csv::Writer::from_writer(vec![]);
-
-    let total_impulse = calculate_total_impulse(system);
-    let center_of_mass = calculate_center_of_mass(system);
-    wtr.serialize((
-        step,
-        time,
-        calculate_system_energy(system),
-        total_impulse.x,
-        total_impulse.y,
-        center_of_mass.x,
-        center_of_mass.y,
-    ))?;
-
-    // here we remove the generated newline character from the csv library so that we can use writeln below.
-    let text = String::from_utf8(wtr.into_inner()?)?.replace('\n', "");
-
-    let mut file = OpenOptions::new().append(true).open(&fullpath)?;
-    writeln!(file, "{}", text)?;
-
     Ok(())
 }
 
-fn write_csv_headers(fullpath: &PathBuf, headers: &Vec<String>) -> Result<(), Box<dyn Error>> {
+/// The record written to an in-memory csv writer, without the newline
+fn into_line(writer: csv::Writer<Vec<u8>>) -> Result<String, ApplicationError> {
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8_lossy(&bytes).replace('\n', ""))
+}
+
+fn write_csv_headers(fullpath: &PathBuf, headers: &Vec<String>) -> Result<(), ApplicationError> {
     let mut wtr = csv::Writer::from_path(fullpath)?;
     wtr.write_record(headers)?;
     wtr.flush()?;
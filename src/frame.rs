@@ -0,0 +1,203 @@
+use kepler_core::{
+    center_of_mass::calculate_center_of_mass,
+    types::{Body, System},
+};
+use maths_rs::Vec2d;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configsystem::TestParticle, diagnostics::calculate_barycentric_velocity,
+    error::ApplicationError,
+};
+
+/// The frame the initial conditions are transformed into before the simulation starts
+///
+/// In the config this is `"as_given"`, `"barycentric"` or `"heliocentric:<body>"`.
+/// A plain `"heliocentric"` uses the most massive body.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Frame {
+    #[default]
+    AsGiven,
+    Barycentric,
+    Heliocentric(Option<String>),
+}
+
+impl TryFrom<String> for Frame {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "as_given" => Ok(Frame::AsGiven),
+            "barycentric" => Ok(Frame::Barycentric),
+            "heliocentric" => Ok(Frame::Heliocentric(None)),
+            _ => match value.strip_prefix("heliocentric:") {
+                Some(body) if !body.is_empty() => Ok(Frame::Heliocentric(Some(body.to_owned()))),
+                _ => Err(format!(
+                    "unknown frame `{value}`, expected `as_given`, `barycentric` or `heliocentric:<body>`"
+                )),
+            },
+        }
+    }
+}
+
+impl From<Frame> for String {
+    fn from(value: Frame) -> Self {
+        match value {
+            Frame::AsGiven => "as_given".to_owned(),
+            Frame::Barycentric => "barycentric".to_owned(),
+            Frame::Heliocentric(None) => "heliocentric".to_owned(),
+            Frame::Heliocentric(Some(body)) => format!("heliocentric:{body}"),
+        }
+    }
+}
+
+pub fn find_body<'a>(system: &'a System, name: &str) -> Result<&'a Body, ApplicationError> {
+    system
+        .bodies
+        .iter()
+        .find(|body| body.name == name)
+        .ok_or_else(|| ApplicationError::UnknownBodyError(name.to_owned()))
+}
+
+/// Moves all bodies and test particles by `position` and `velocity`
+fn shift(
+    system: &mut System,
+    test_particles: &mut [TestParticle],
+    position: Vec2d,
+    velocity: Vec2d,
+) {
+    for body in system.bodies.iter_mut() {
+        body.position = body.position - position;
+        body.velocity = body.velocity - velocity;
+    }
+    for particle in test_particles.iter_mut() {
+        particle.position = particle.position - position;
+        particle.velocity = particle.velocity - velocity;
+    }
+}
+
+/// Transforms the initial conditions so the origin of `frame` is at rest at the origin
+pub fn apply_frame(
+    frame: &Frame,
+    system: &mut System,
+    test_particles: &mut [TestParticle],
+) -> Result<(), ApplicationError> {
+    let (position, velocity) = match frame {
+        Frame::AsGiven => return Ok(()),
+        Frame::Barycentric => (
+            calculate_center_of_mass(system),
+            calculate_barycentric_velocity(system),
+        ),
+        Frame::Heliocentric(name) => {
+            let body = match name {
+                Some(name) => find_body(system, name)?,
+                None => system
+                    .bodies
+                    .iter()
+                    .max_by(|a, b| a.mass.total_cmp(&b.mass))
+                    .ok_or_else(|| {
+                        ApplicationError::UnknownBodyError("<empty system>".to_owned())
+                    })?,
+            };
+            (body.position, body.velocity)
+        }
+    };
+    shift(system, test_particles, position, velocity);
+    Ok(())
+}
+
+/// Returns copies of the system and the test particles with positions and
+/// velocities relative to the body with the given name
+pub fn relative_to(
+    name: &str,
+    system: &System,
+    test_particles: &[TestParticle],
+) -> Result<(System, Vec<TestParticle>), ApplicationError> {
+    let body = find_body(system, name)?;
+    let (position, velocity) = (body.position, body.velocity);
+    let mut system = system.clone();
+    let mut test_particles = test_particles.to_vec();
+    shift(&mut system, &mut test_particles, position, velocity);
+    Ok((system, test_particles))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply_frame, Frame};
+    use kepler_core::types::{Body, System};
+    use maths_rs::Vec2d;
+
+    fn system() -> System {
+        System {
+            bodies: vec![
+                Body {
+                    name: "Sun".to_owned(),
+                    mass: 3.0,
+                    position: Vec2d::new(1.0, 2.0),
+                    velocity: Vec2d::new(3.0, 4.0),
+                },
+                Body {
+                    name: "Earth".to_owned(),
+                    mass: 1.0,
+                    position: Vec2d::new(5.0, 2.0),
+                    velocity: Vec2d::new(3.0, 8.0),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    pub fn parses_frames() {
+        assert_eq!(
+            Frame::try_from("barycentric".to_owned()),
+            Ok(Frame::Barycentric)
+        );
+        assert_eq!(
+            Frame::try_from("heliocentric:Sun".to_owned()),
+            Ok(Frame::Heliocentric(Some("Sun".to_owned())))
+        );
+        assert!(Frame::try_from("heliocentric:".to_owned()).is_err());
+        assert!(Frame::try_from("galactic".to_owned()).is_err());
+    }
+
+    #[test]
+    pub fn moves_the_barycenter_to_the_origin() {
+        let mut system = system();
+        apply_frame(&Frame::Barycentric, &mut system, &mut []).unwrap();
+        assert_eq!(
+            (system.bodies[0].position.x, system.bodies[0].position.y),
+            (-1.0, 0.0)
+        );
+        assert_eq!(
+            (system.bodies[1].position.x, system.bodies[1].position.y),
+            (3.0, 0.0)
+        );
+        assert_eq!(
+            (system.bodies[0].velocity.x, system.bodies[0].velocity.y),
+            (0.0, -1.0)
+        );
+        assert_eq!(
+            (system.bodies[1].velocity.x, system.bodies[1].velocity.y),
+            (0.0, 3.0)
+        );
+    }
+
+    #[test]
+    pub fn puts_the_heaviest_body_at_rest() {
+        let mut system = system();
+        apply_frame(&Frame::Heliocentric(None), &mut system, &mut []).unwrap();
+        assert_eq!(
+            (system.bodies[0].position.x, system.bodies[0].position.y),
+            (0.0, 0.0)
+        );
+        assert_eq!(
+            (system.bodies[0].velocity.x, system.bodies[0].velocity.y),
+            (0.0, 0.0)
+        );
+        assert_eq!(
+            (system.bodies[1].velocity.x, system.bodies[1].velocity.y),
+            (0.0, 4.0)
+        );
+    }
+}
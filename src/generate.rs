@@ -0,0 +1,378 @@
+use std::{collections::BTreeMap, f64::consts::PI};
+
+use kepler_core::types::{Body, System};
+use maths_rs::Vec2d;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    configsystem::{Config, ConfigSystem, SystemParameter, TestParticle},
+    diagnostics::{calculate_total_kinetic_energy, calculate_total_potential_energy},
+    frame::Frame,
+    gravity::GRAVITATIONAL_CONSTANT,
+};
+
+pub const SUN_MASS: f64 = 1.989e30;
+pub const EARTH_MASS: f64 = 5.972e24;
+pub const JUPITER_MASS: f64 = 1.898e27;
+pub const ASTRONOMICAL_UNIT: f64 = 1.495978707e11;
+
+/// A config that resolves the shortest period with about 1000 steps
+/// and runs long enough to complete the longest period once.
+pub fn config_for_periods(shortest_period: f64, longest_period: f64) -> Config {
+    let timestep = shortest_period / 1000.0;
+    let steps = (longest_period / timestep).ceil().max(1.0) as i64;
+    Config {
+        timestep,
+        steps,
+        export_step: (steps / 1000).max(1),
+        export_directory: "export_files".to_owned(),
+        export_file_name_prefix: "SIM".to_owned(),
+        export_system_state: false,
+        export_body_history: true,
+        export_system_parameters_history: true,
+        system_parameters_columns: SystemParameter::all(),
+        plot_system: true,
+        plot_system_kinetic_energy: false,
+        plot_system_potential_energy: false,
+        export_test_particle_history: false,
+        threads: None,
+        frame: Frame::AsGiven,
+        export_relative_to: None,
+        stdout: None,
+        reverse: false,
+        export_min_distances: false,
+        close_encounter_distance: None,
+        strict_stability: false,
+        export_chaos_indicators: false,
+        shadow_perturbation: Config::default_shadow_perturbation(),
+    }
+}
+
+pub fn orbital_period(central_mass: f64, radius: f64) -> f64 {
+    2.0 * PI * (radius.powi(3) / (GRAVITATIONAL_CONSTANT * central_mass)).sqrt()
+}
+
+pub fn circular_speed(central_mass: f64, radius: f64) -> f64 {
+    (GRAVITATIONAL_CONSTANT * central_mass / radius).sqrt()
+}
+
+/// Draws a number between `min` and `max` that is uniformly distributed in log space.
+fn log_uniform(rng: &mut ChaCha8Rng, min: f64, max: f64) -> f64 {
+    (rng.gen_range(min.ln()..=max.ln())).exp()
+}
+
+pub fn polar(radius: f64, angle: f64) -> Vec2d {
+    Vec2d::new(radius * angle.cos(), radius * angle.sin())
+}
+
+/// Shifts the velocities so the total momentum of the bodies vanishes
+/// and the system does not drift away.
+pub fn remove_total_momentum(bodies: &mut [Body]) {
+    let total_mass: f64 = bodies.iter().map(|body| body.mass).sum();
+    let momentum = bodies.iter().fold(Vec2d::new(0.0, 0.0), |momentum, body| {
+        momentum + body.velocity * body.mass
+    });
+    let drift = momentum * (1.0 / total_mass);
+    for body in bodies.iter_mut() {
+        body.velocity = body.velocity - drift;
+    }
+}
+
+/// A star with planets on orbits with log-uniform distances and masses
+/// and eccentricities up to `max_eccentricity`
+pub fn generate_planets(
+    rng: &mut ChaCha8Rng,
+    count: usize,
+    star_mass: f64,
+    (min_mass, max_mass): (f64, f64),
+    (min_distance, max_distance): (f64, f64),
+    max_eccentricity: f64,
+) -> ConfigSystem {
+    let mut distances: Vec<f64> = (0..count)
+        .map(|_| log_uniform(rng, min_distance, max_distance))
+        .collect();
+    distances.sort_by(f64::total_cmp);
+
+    let mut bodies = vec![Body {
+        name: "Star".to_owned(),
+        mass: star_mass,
+        position: Vec2d::new(0.0, 0.0),
+        velocity: Vec2d::new(0.0, 0.0),
+    }];
+    for (index, distance) in distances.iter().enumerate() {
+        let mass = log_uniform(rng, min_mass, max_mass);
+        let eccentricity = rng.gen_range(0.0..=max_eccentricity);
+        let angle = rng.gen_range(0.0..2.0 * PI);
+        // every planet starts at its pericenter
+        let speed = circular_speed(star_mass, *distance) * (1.0 + eccentricity).sqrt();
+        bodies.push(Body {
+            name: format!("Planet {}", index + 1),
+            mass,
+            position: polar(*distance, angle),
+            velocity: polar(speed, angle + PI / 2.0),
+        });
+    }
+    remove_total_momentum(&mut bodies);
+
+    let shortest_period = orbital_period(star_mass, min_distance.min(max_distance));
+    let longest_period =
+        orbital_period(star_mass, distances.last().copied().unwrap_or(max_distance));
+    ConfigSystem {
+        config: config_for_periods(shortest_period, longest_period),
+        system: System { bodies },
+        test_particles: vec![],
+        body_properties: BTreeMap::new(),
+    }
+}
+
+/// Samples a Plummer sphere with the method of Aarseth, Hénon and Wielen (1974)
+/// in units where G = M = a = 1 and keeps the x and y components.
+///
+/// The projected cluster has less kinetic energy than a planar cluster in
+/// equilibrium needs, so the velocities are scaled to 2K = |U|. The cluster is
+/// then in virial equilibrium as a whole, but its velocity distribution is still
+/// that of the projected sphere and it relaxes towards a planar equilibrium.
+pub fn generate_plummer(
+    rng: &mut ChaCha8Rng,
+    count: usize,
+    total_mass: f64,
+    scale_radius: f64,
+) -> ConfigSystem {
+    let velocity_scale = (GRAVITATIONAL_CONSTANT * total_mass / scale_radius).sqrt();
+
+    let mut bodies = Vec::with_capacity(count);
+    for index in 0..count {
+        // cut off the few stars that would be placed very far out
+        let radius = loop {
+            let x: f64 = rng.gen_range(f64::EPSILON..1.0);
+            let radius = 1.0 / (x.powf(-2.0 / 3.0) - 1.0).sqrt();
+            if radius < 10.0 {
+                break radius;
+            }
+        };
+        let escape_speed = 2.0_f64.sqrt() * (1.0 + radius * radius).powf(-0.25);
+        // von Neumann rejection for g(q) = q² (1 - q²)^3.5
+        let q = loop {
+            let q: f64 = rng.gen_range(0.0..1.0);
+            let g: f64 = rng.gen_range(0.0..0.1);
+            if g < q * q * (1.0 - q * q).powf(3.5) {
+                break q;
+            }
+        };
+        let [x, y, _] = random_direction(rng);
+        let [vx, vy, _] = random_direction(rng);
+        let speed = q * escape_speed * velocity_scale;
+        bodies.push(Body {
+            name: format!("Star {}", index + 1),
+            mass: total_mass / count as f64,
+            position: Vec2d::new(x, y) * (radius * scale_radius),
+            velocity: Vec2d::new(vx, vy) * speed,
+        });
+    }
+    remove_total_momentum(&mut bodies);
+    let mut system = System { bodies };
+    let kinetic_energy = calculate_total_kinetic_energy(&system);
+    let potential_energy = calculate_total_potential_energy(&system);
+    if kinetic_energy > 0.0 && potential_energy < 0.0 {
+        let virial_scale = (potential_energy.abs() / (2.0 * kinetic_energy)).sqrt();
+        for body in system.bodies.iter_mut() {
+            body.velocity = body.velocity * virial_scale;
+        }
+    }
+
+    // the crossing time of the cluster is about a / v
+    let crossing_time = scale_radius / velocity_scale;
+    ConfigSystem {
+        config: config_for_periods(crossing_time, 10.0 * crossing_time),
+        system,
+        test_particles: vec![],
+        body_properties: BTreeMap::new(),
+    }
+}
+
+/// An isotropically distributed 3D unit vector
+fn random_direction(rng: &mut ChaCha8Rng) -> [f64; 3] {
+    let z: f64 = rng.gen_range(-1.0..=1.0);
+    let angle = rng.gen_range(0.0..2.0 * PI);
+    let r = (1.0 - z * z).sqrt();
+    [r * angle.cos(), r * angle.sin(), z]
+}
+
+/// A star with test particles on circular orbits between the two radii
+pub fn generate_ring(
+    rng: &mut ChaCha8Rng,
+    count: usize,
+    star_mass: f64,
+    inner_radius: f64,
+    outer_radius: f64,
+) -> ConfigSystem {
+    let bodies = vec![Body {
+        name: "Star".to_owned(),
+        mass: star_mass,
+        position: Vec2d::new(0.0, 0.0),
+        velocity: Vec2d::new(0.0, 0.0),
+    }];
+    let test_particles = (0..count)
+        .map(|_| {
+            // uniform in area between the two radii
+            let u: f64 = rng.gen_range(0.0..=1.0);
+            let radius = (inner_radius * inner_radius
+                + u * (outer_radius * outer_radius - inner_radius * inner_radius))
+                .sqrt();
+            let angle = rng.gen_range(0.0..2.0 * PI);
+            TestParticle {
+                position: polar(radius, angle),
+                velocity: polar(circular_speed(star_mass, radius), angle + PI / 2.0),
+            }
+        })
+        .collect();
+
+    let mut config = config_for_periods(
+        orbital_period(star_mass, inner_radius),
+        orbital_period(star_mass, outer_radius),
+    );
+    config.export_test_particle_history = true;
+    ConfigSystem {
+        config,
+        system: System { bodies },
+        test_particles,
+        body_properties: BTreeMap::new(),
+    }
+}
+
+/// Two stars on a circular orbit with planets on circular orbits about the
+/// barycenter of both stars, well outside the binary
+pub fn generate_binary(
+    rng: &mut ChaCha8Rng,
+    primary_mass: f64,
+    secondary_mass: f64,
+    separation: f64,
+    planets: usize,
+) -> ConfigSystem {
+    let total_mass = primary_mass + secondary_mass;
+    let angle = rng.gen_range(0.0..2.0 * PI);
+    let relative_speed = circular_speed(total_mass, separation);
+
+    let mut bodies = vec![
+        Body {
+            name: "Primary".to_owned(),
+            mass: primary_mass,
+            position: polar(separation * secondary_mass / total_mass, angle),
+            velocity: polar(
+                relative_speed * secondary_mass / total_mass,
+                angle + PI / 2.0,
+            ),
+        },
+        Body {
+            name: "Secondary".to_owned(),
+            mass: secondary_mass,
+            position: polar(separation * primary_mass / total_mass, angle + PI),
+            velocity: polar(relative_speed * primary_mass / total_mass, angle + 1.5 * PI),
+        },
+    ];
+
+    // circumbinary orbits are only stable well outside the binary
+    let mut distances: Vec<f64> = (0..planets)
+        .map(|_| log_uniform(rng, 3.0 * separation, 10.0 * separation))
+        .collect();
+    distances.sort_by(f64::total_cmp);
+    for (index, distance) in distances.iter().enumerate() {
+        let angle = rng.gen_range(0.0..2.0 * PI);
+        bodies.push(Body {
+            name: format!("Planet {}", index + 1),
+            mass: log_uniform(rng, EARTH_MASS, JUPITER_MASS),
+            position: polar(*distance, angle),
+            velocity: polar(circular_speed(total_mass, *distance), angle + PI / 2.0),
+        });
+    }
+    remove_total_momentum(&mut bodies);
+
+    let longest_period =
+        orbital_period(total_mass, distances.last().copied().unwrap_or(separation));
+    ConfigSystem {
+        config: config_for_periods(orbital_period(total_mass, separation), longest_period),
+        system: System { bodies },
+        test_particles: vec![],
+        body_properties: BTreeMap::new(),
+    }
+}
+
+/// Initial conditions of Chenciner and Montgomery (2000) for G = m = 1
+pub fn generate_figure_eight(
+    rng: &mut ChaCha8Rng,
+    mass: f64,
+    scale: f64,
+    perturbation: f64,
+) -> ConfigSystem {
+    let position = Vec2d::new(0.97000436, -0.24308753);
+    let velocity = Vec2d::new(-0.93240737, -0.86473146);
+    let velocity_scale = (GRAVITATIONAL_CONSTANT * mass / scale).sqrt();
+
+    let initial_states = [
+        ("Body 1", position, velocity * -0.5),
+        ("Body 2", position * -1.0, velocity * -0.5),
+        ("Body 3", Vec2d::new(0.0, 0.0), velocity),
+    ];
+    let mut bodies: Vec<Body> = initial_states
+        .into_iter()
+        .map(|(name, position, velocity)| {
+            let noise = if perturbation > 0.0 {
+                1.0 + rng.gen_range(-perturbation..=perturbation)
+            } else {
+                1.0
+            };
+            Body {
+                name: name.to_owned(),
+                mass,
+                position: position * scale,
+                velocity: velocity * (velocity_scale * noise),
+            }
+        })
+        .collect();
+    remove_total_momentum(&mut bodies);
+
+    let period = 6.32591398 * (scale.powi(3) / (GRAVITATIONAL_CONSTANT * mass)).sqrt();
+    ConfigSystem {
+        config: config_for_periods(period, period),
+        system: System { bodies },
+        test_particles: vec![],
+        body_properties: BTreeMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::{generate_planets, generate_plummer, ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS};
+    use crate::diagnostics::calculate_virial_ratio;
+
+    #[test]
+    pub fn same_seed_gives_same_system() {
+        let generate = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let configsystem = generate_planets(
+                &mut rng,
+                5,
+                SUN_MASS,
+                (EARTH_MASS, 100.0 * EARTH_MASS),
+                (ASTRONOMICAL_UNIT, 10.0 * ASTRONOMICAL_UNIT),
+                0.05,
+            );
+            toml::to_string(&configsystem).unwrap()
+        };
+        assert_eq!(generate(42), generate(42));
+        assert_ne!(generate(42), generate(43));
+    }
+
+    #[test]
+    pub fn plummer_cluster_is_virialised_in_the_plane() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let configsystem =
+            generate_plummer(&mut rng, 200, 100.0 * SUN_MASS, 1000.0 * ASTRONOMICAL_UNIT);
+        let virial_ratio = calculate_virial_ratio(&configsystem.system);
+        assert!((virial_ratio - 1.0).abs() < 1e-9, "{virial_ratio}");
+    }
+}
@@ -0,0 +1,121 @@
+use kepler_core::types::System;
+use maths_rs::Vec2d;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::configsystem::TestParticle;
+
+/// Newton's gravitational constant in m³/(kg·s²)
+pub const GRAVITATIONAL_CONSTANT: f64 = 6.6743e-11;
+
+/// Calculates the gravitational acceleration on the body with the given index
+/// caused by all other bodies of the system.
+///
+/// The contributions are always summed up in body order, so the result does not
+/// depend on how the bodies are distributed over threads.
+#[cfg(feature = "parallel")]
+pub fn calculate_acceleration(index: usize, system: &System) -> Vec2d {
+    calculate_acceleration_at(system.bodies[index].position, system, Some(index))
+}
+
+/// Calculates the gravitational acceleration at `position` caused by the bodies
+/// of the system, leaving out the body with index `skip`.
+pub fn calculate_acceleration_at(position: Vec2d, system: &System, skip: Option<usize>) -> Vec2d {
+    let mut acceleration = Vec2d::new(0.0, 0.0);
+    for (other_index, other) in system.bodies.iter().enumerate() {
+        if Some(other_index) == skip {
+            continue;
+        }
+        let distance = other.position - position;
+        let r = (distance.x * distance.x + distance.y * distance.y).sqrt();
+        acceleration =
+            acceleration + distance * (GRAVITATIONAL_CONSTANT * other.mass / (r * r * r));
+    }
+    acceleration
+}
+
+#[cfg(feature = "parallel")]
+pub fn calculate_accelerations_parallel(system: &System) -> Vec<Vec2d> {
+    (0..system.bodies.len())
+        .into_par_iter()
+        .map(|index| calculate_acceleration(index, system))
+        .collect()
+}
+
+pub fn calculate_test_particle_accelerations(
+    test_particles: &[TestParticle],
+    system: &System,
+) -> Vec<Vec2d> {
+    test_particles
+        .iter()
+        .map(|particle| calculate_acceleration_at(particle.position, system, None))
+        .collect()
+}
+
+#[cfg(feature = "parallel")]
+pub fn calculate_test_particle_accelerations_parallel(
+    test_particles: &[TestParticle],
+    system: &System,
+) -> Vec<Vec2d> {
+    test_particles
+        .par_iter()
+        .map(|particle| calculate_acceleration_at(particle.position, system, None))
+        .collect()
+}
+
+/// Semi-implicit Euler step: the velocities are updated first and the new
+/// velocities are used to move the bodies.
+#[cfg(feature = "parallel")]
+pub fn apply_accelerations(mut system: System, accelerations: &[Vec2d], timestep: f64) -> System {
+    for (body, acceleration) in system.bodies.iter_mut().zip(accelerations) {
+        body.velocity = body.velocity + *acceleration * timestep;
+        body.position = body.position + body.velocity * timestep;
+    }
+    system
+}
+
+/// The same semi-implicit Euler step as `apply_accelerations`, for test particles.
+pub fn move_test_particles(
+    test_particles: &mut [TestParticle],
+    accelerations: &[Vec2d],
+    timestep: f64,
+) {
+    for (particle, acceleration) in test_particles.iter_mut().zip(accelerations) {
+        particle.velocity = particle.velocity + *acceleration * timestep;
+        particle.position = particle.position + particle.velocity * timestep;
+    }
+}
+
+#[cfg(all(test, feature = "parallel"))]
+mod test {
+    use super::{calculate_acceleration, calculate_accelerations_parallel};
+    use kepler_core::types::{Body, System};
+    use maths_rs::Vec2d;
+
+    #[test]
+    pub fn parallel_accelerations_match_serial() {
+        let bodies = (0..64)
+            .map(|i| Body {
+                name: format!("Body {i}"),
+                mass: 1e24 * (1.0 + i as f64),
+                position: Vec2d::new(1e9 * (i as f64).cos() * i as f64, 1e9 * (i as f64).sin()),
+                velocity: Vec2d::new(0.0, 0.0),
+            })
+            .collect();
+        let system = System { bodies };
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let parallel = pool.install(|| calculate_accelerations_parallel(&system));
+        let serial: Vec<Vec2d> = (0..system.bodies.len())
+            .map(|index| calculate_acceleration(index, &system))
+            .collect();
+
+        for (p, s) in parallel.iter().zip(serial.iter()) {
+            assert_eq!(p.x.to_bits(), s.x.to_bits());
+            assert_eq!(p.y.to_bits(), s.y.to_bits());
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Simulation of planetary motion on top of [`kepler_core`]
+//!
+//! A simulation is described by a [`configsystem::ConfigSystem`], usually read
+//! from a toml, JSON, YAML or RON file, and run with a [`simulation::Simulation`]:
+//!
+//! ```no_run
+//! use kepler_cli::{configsystem::ConfigSystem, simulation::Simulation};
+//!
+//! let configsystem = ConfigSystem::parse("example.toml".to_owned())?;
+//! let mut simulation = Simulation::builder(configsystem.config, configsystem.system)
+//!     .test_particles(configsystem.test_particles)
+//!     .build()?;
+//! while !simulation.is_done() {
+//!     simulation.step()?;
+//! }
+//! let (system, summary) = simulation.finish();
+//! # Ok::<(), kepler_cli::error::ApplicationError>(())
+//! ```
+//!
+//! The exports enabled in the config are written while the simulation runs.
+//! Implement [`simulation::Monitor`] to follow a simulation step by step.
+
+/// Lyapunov exponent and MEGNO from a shadow system
+pub mod chaos;
+/// Comparing the trajectories of two runs for the `compare` subcommand
+pub mod compare;
+/// The config file: simulation settings, bodies and test particles
+pub mod configsystem;
+/// Reading and replacing single values of a config by their path
+pub mod configvalue;
+/// Runs at halved timesteps for the `convergence` subcommand
+pub mod convergence;
+/// Energies, momenta and other properties of a system
+pub mod diagnostics;
+pub mod error;
+/// Writing the state of a simulation to csv and JSON files
+pub mod export;
+/// Reference frames of the initial conditions and the exports
+pub mod frame;
+/// Generated systems for the `generate` subcommand
+pub mod generate;
+/// Gravitational accelerations and the integrator used by the parallel mover
+pub mod gravity;
+/// Config includes and libraries of bodies
+pub mod library;
+/// Advancing a system by one timestep, single- or multi-threaded
+pub mod mover;
+/// Periodograms and resonances for the `periodogram` subcommand
+pub mod periodogram;
+/// Energy plots
+pub mod plot;
+/// Built-in systems for `init --preset` and `run --preset`
+pub mod preset;
+/// Python bindings, enabled with the `python` feature
+#[cfg(feature = "python")]
+pub mod python;
+/// Integrating forward and back for the `reversibility-check` subcommand
+pub mod reversibility;
+/// The simulation loop
+pub mod simulation;
+/// Hill stability and Roche limit checks of the initial state
+pub mod stability;
+/// Key figures of a finished run
+pub mod summary;
+/// Reading the exported trajectories of a run back
+pub mod trajectory;
@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    configsystem::{ConfigFormat, ConfigSystem},
+    error::ApplicationError,
+};
+
+/// The key listing the files to include, relative to the including file
+const INCLUDE: &str = "include";
+/// The key of a body referring to a library body, like `from = "planets.Jupiter"`
+const FROM: &str = "from";
+/// Top level keys of a config that are not body libraries
+const CONFIG_KEYS: [&str; 3] = ["config", "system", INCLUDE];
+
+/// A body of a library together with the file that defines it
+struct LibraryBody {
+    body: Map<String, Value>,
+    file: PathBuf,
+}
+
+/// All libraries by name. Later definitions of a body win, so a config can
+/// replace bodies of the files it includes.
+#[derive(Default)]
+struct Libraries(BTreeMap<String, Vec<LibraryBody>>);
+
+fn include_error(file: &Path, message: impl std::fmt::Display) -> ApplicationError {
+    ApplicationError::IncludeError(format!("{}: {message}", file.display()))
+}
+
+/// Whether the config uses includes or library bodies
+pub fn needs_resolution(value: &Value) -> bool {
+    let bodies = value
+        .pointer("/system/bodies")
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+    value.get(INCLUDE).is_some() || bodies.iter().any(|body| body.get(FROM).is_some())
+}
+
+fn read(file: &Path, included_by: &Path) -> Result<Value, ApplicationError> {
+    let contents = std::fs::read_to_string(file).map_err(|e| {
+        include_error(
+            included_by,
+            format_args!("cannot read included file {}: {e}", file.display()),
+        )
+    })?;
+    ConfigSystem::value_from_str_as(&contents, ConfigFormat::detect(None, file)).map_err(|e| {
+        include_error(
+            included_by,
+            format_args!("cannot parse included file {}: {e}", file.display()),
+        )
+    })
+}
+
+/// Adds the libraries of the files `value` includes and then its own libraries.
+/// `stack` holds the files that are currently being included, to detect cycles.
+fn collect(
+    file: &Path,
+    value: &Value,
+    stack: &mut Vec<PathBuf>,
+    libraries: &mut Libraries,
+) -> Result<(), ApplicationError> {
+    let Some(table) = value.as_object() else {
+        return Err(include_error(file, "the config is not a table"));
+    };
+
+    let includes = match table.get(INCLUDE) {
+        None => vec![],
+        Some(Value::Array(includes)) => includes
+            .iter()
+            .map(|include| {
+                include
+                    .as_str()
+                    .ok_or_else(|| include_error(file, "`include` has to be a list of paths"))
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(include_error(file, "`include` has to be a list of paths")),
+    };
+    let directory = file.parent().unwrap_or(Path::new(""));
+    for include in includes {
+        let included = directory.join(include);
+        let canonical = included.canonicalize().map_err(|e| {
+            include_error(
+                file,
+                format_args!("cannot read included file {}: {e}", included.display()),
+            )
+        })?;
+        if let Some(start) = stack.iter().position(|path| *path == canonical) {
+            let cycle: Vec<String> = stack[start..]
+                .iter()
+                .chain(std::iter::once(&canonical))
+                .map(|path| path.display().to_string())
+                .collect();
+            return Err(include_error(
+                file,
+                format_args!("include cycle {}", cycle.join(" -> ")),
+            ));
+        }
+        let value = read(&included, file)?;
+        if value.get("config").is_some() || value.get("system").is_some() {
+            return Err(include_error(
+                &included,
+                "included files can only contain body libraries, not `config` or `system`",
+            ));
+        }
+        stack.push(canonical);
+        collect(&included, &value, stack, libraries)?;
+        stack.pop();
+    }
+
+    for (name, bodies) in table.iter() {
+        if CONFIG_KEYS.contains(&name.as_str()) {
+            continue;
+        }
+        let not_a_library = || {
+            include_error(
+                file,
+                format_args!("`{name}` is not a body library, expected a list of bodies"),
+            )
+        };
+        let bodies = bodies.as_array().ok_or_else(not_a_library)?;
+        for body in bodies {
+            let body = body.as_object().ok_or_else(not_a_library)?;
+            if !body.get("name").is_some_and(Value::is_string) {
+                return Err(include_error(
+                    file,
+                    format_args!("a body of the library `{name}` has no name"),
+                ));
+            }
+            libraries
+                .0
+                .entry(name.clone())
+                .or_default()
+                .push(LibraryBody {
+                    body: body.clone(),
+                    file: file.to_owned(),
+                });
+        }
+    }
+    Ok(())
+}
+
+impl Libraries {
+    fn find(&self, file: &Path, reference: &str) -> Result<&Map<String, Value>, ApplicationError> {
+        let (library, name) = reference.split_once('.').ok_or_else(|| {
+            include_error(
+                file,
+                format_args!("invalid `from = \"{reference}\"`, expected `<library>.<body>`"),
+            )
+        })?;
+        let bodies = self.0.get(library).ok_or_else(|| {
+            let known: Vec<&str> = self.0.keys().map(String::as_str).collect();
+            include_error(
+                file,
+                format_args!(
+                    "unknown library `{library}` in `from = \"{reference}\"`, known libraries: {}",
+                    if known.is_empty() {
+                        "none".to_owned()
+                    } else {
+                        known.join(", ")
+                    }
+                ),
+            )
+        })?;
+        bodies
+            .iter()
+            .rev()
+            .find(|body| body.body.get("name").and_then(Value::as_str) == Some(name))
+            .map(|body| &body.body)
+            .ok_or_else(|| {
+                let files: BTreeSet<String> = bodies
+                    .iter()
+                    .map(|body| body.file.display().to_string())
+                    .collect();
+                include_error(
+                    file,
+                    format_args!(
+                        "there is no body `{name}` in the library `{library}` defined in {}",
+                        Vec::from_iter(files).join(", ")
+                    ),
+                )
+            })
+    }
+}
+
+/// Replaces the values of `base` with the ones of `overrides`, tables are merged
+/// so e.g. only the `y` of a velocity can be replaced
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Loads the included files of the config in `file` and replaces all bodies
+/// with a `from` key by the library body, merged with the other keys of the body
+pub fn resolve(file: &Path, mut value: Value) -> Result<Value, ApplicationError> {
+    let mut libraries = Libraries::default();
+    let canonical = file.canonicalize()?;
+    collect(file, &value, &mut vec![canonical], &mut libraries)?;
+
+    let Some(bodies) = value
+        .pointer_mut("/system/bodies")
+        .and_then(Value::as_array_mut)
+    else {
+        return Ok(value);
+    };
+    for body in bodies.iter_mut() {
+        let Some(reference) = body.as_object_mut().and_then(|body| body.remove(FROM)) else {
+            continue;
+        };
+        let reference = reference.as_str().ok_or_else(|| {
+            include_error(file, "`from` has to be a string like \"planets.Jupiter\"")
+        })?;
+        let mut resolved = Value::Object(libraries.find(file, reference)?.clone());
+        merge(&mut resolved, std::mem::take(body));
+        *body = resolved;
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::configsystem::ConfigSystem;
+    use std::fs;
+
+    #[test]
+    pub fn resolves_library_bodies_and_detects_cycles() {
+        let directory =
+            std::env::temp_dir().join(format!("kepler_cli_library_{}", std::process::id()));
+        fs::create_dir_all(directory.join("bodies")).unwrap();
+        fs::write(
+            directory.join("bodies/planets.toml"),
+            r#"
+            include = ["stars.toml"]
+
+            [[planets]]
+            name = "Jupiter"
+            mass = 1.898e27
+            position = { x = 7.78e11, y = 0.0 }
+            velocity = { x = 0.0, y = 1.307e4 }
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            directory.join("bodies/stars.toml"),
+            r#"
+            [[stars]]
+            name = "Sun"
+            mass = 1.989e30
+            position = { x = 0.0, y = 0.0 }
+            velocity = { x = 0.0, y = 0.0 }
+            "#,
+        )
+        .unwrap();
+        let config = r#"
+            [config]
+            timestep = 1.0
+            steps = 10
+            export_step = 1
+            export_directory = "export_files"
+            export_file_name_prefix = "SIM"
+        "#;
+        fs::write(
+            directory.join("system.toml"),
+            format!(
+                r#"
+                include = ["bodies/planets.toml"]
+                {config}
+                [[system.bodies]]
+                from = "stars.Sun"
+
+                [[system.bodies]]
+                from = "planets.Jupiter"
+                name = "Hot Jupiter"
+                position = {{ x = 7.0e9 }}
+                "#
+            ),
+        )
+        .unwrap();
+
+        let configsystem =
+            ConfigSystem::parse(directory.join("system.toml").to_string_lossy().into_owned())
+                .unwrap();
+        let bodies = &configsystem.system.bodies;
+        assert_eq!(bodies[0].name, "Sun");
+        assert_eq!(bodies[1].name, "Hot Jupiter");
+        assert_eq!(bodies[1].mass, 1.898e27);
+        assert_eq!((bodies[1].position.x, bodies[1].position.y), (7.0e9, 0.0));
+
+        // stars.toml including planets.toml closes a cycle
+        fs::write(
+            directory.join("bodies/stars.toml"),
+            r#"include = ["planets.toml"]"#,
+        )
+        .unwrap();
+        let error =
+            ConfigSystem::parse(directory.join("system.toml").to_string_lossy().into_owned())
+                .unwrap_err()
+                .to_string();
+        assert!(error.contains("include cycle"), "{error}");
+        assert!(error.contains("stars.toml"), "{error}");
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,48 +1,209 @@
-use crate::simulation::run_simulation;
-use clap::Parser;
-use configsystem::ConfigSystem;
-
-mod configsystem;
-mod error;
-mod export;
-mod simulation;
-
-mod plot;
+use std::process::ExitCode;
 
+use clap::{Parser, Subcommand};
+use cli::{
+    compare::{self, CompareArgs},
+    convergence::{self, ConvergenceArgs},
+    convert::{self, ConvertArgs},
+    generate::{self, GenerateArgs},
+    periodogram::{self, PeriodogramArgs},
+    preset::{self, InitArgs},
+    progress::{self, Progress},
+    reversibility::{self, ReversibilityArgs},
+    serve::{self, ServeArgs},
+    sweep::{self, SweepArgs},
+    tui::{LogWriter, Tui},
+    validate::{self, ValidateArgs},
+    OverrideArgs,
+};
+use kepler_cli::{
+    configsystem::{ConfigFormat, ConfigSystem, StdoutFormat},
+    configvalue::apply_overrides,
+    error::ApplicationError,
+    preset::Preset,
+    simulation::{Monitor, Simulation},
+};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod cli;
+
 /// Command line interface for the Kepler planetary motion simulator
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-struct Args {
-    /// Path to the toml file with the simulation details
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the simulation described in a config file
+    Run(RunArgs),
+    /// Write the config file of a built-in system
+    Init(InitArgs),
+    /// Write a config file for a generated system
+    Generate(GenerateArgs),
+    /// Run variants of a config with some parameters varied
+    Sweep(SweepArgs),
+    /// Run a simulation while drawing it live on a local web page
+    Serve(ServeArgs),
+    /// Rewrite a config in another format
+    Convert(ConvertArgs),
+    /// Run a config forward and back and report how far it ends up from the initial state
+    ReversibilityCheck(ReversibilityArgs),
+    /// Run a config at halved timesteps and estimate the order of the integrator
+    Convergence(ConvergenceArgs),
+    /// Compare the trajectories of two runs and report how far they diverge
+    Compare(CompareArgs),
+    /// Read a config and check the system for obvious instabilities
+    Validate(ValidateArgs),
+    /// Find the dominant periods of the bodies of a run and resonances between them
+    Periodogram(PeriodogramArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    /// Path to the config file with the simulation details
+    #[arg(short, long, required_unless_present = "preset")]
+    filename: Option<String>,
+
+    /// Run a built-in system instead of a config file
+    #[arg(long, value_enum, conflicts_with_all = ["filename", "format"])]
+    preset: Option<Preset>,
+
+    /// Format of the config file, taken from the file extension if not given
+    #[arg(long, value_enum)]
+    format: Option<ConfigFormat>,
+
+    /// Number of threads used for the force evaluation, overrides `threads` in the config
     #[arg(short, long)]
-    filename: String,
+    threads: Option<usize>,
+
+    /// Stream every export step to stdout, overrides `stdout` in the config
+    #[arg(long, value_enum)]
+    stdout: Option<StdoutFormat>,
+
+    /// Integrate backwards in time, overrides `reverse` in the config
+    #[arg(long)]
+    reverse: bool,
+
+    /// Show the running simulation in the terminal
+    #[arg(long)]
+    tui: bool,
+
+    #[command(flatten)]
+    overrides: OverrideArgs,
 }
 
-fn main() {
+fn main() -> ExitCode {
     tracing_subscriber::registry()
         .with(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "warn".into()),
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| format!("warn,{}=info", progress::LOG_TARGET).into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(|| LogWriter))
         .init();
 
-    let args = Args::parse();
+    match Cli::parse().command {
+        Command::Run(args) => run(args),
+        Command::Init(args) => exit_code(preset::init(args), "Error when writing preset"),
+        Command::Generate(args) => {
+            exit_code(generate::generate(args), "Error when generating system")
+        }
+        Command::Sweep(args) => exit_code(sweep::sweep(args), "Error when running sweep"),
+        Command::Serve(args) => exit_code(serve::serve(args), "Error when serving simulation"),
+        Command::Convert(args) => exit_code(convert::convert(args), "Error when converting config"),
+        Command::ReversibilityCheck(args) => exit_code(
+            reversibility::reversibility_check(args),
+            "Error when checking reversibility",
+        ),
+        Command::Convergence(args) => exit_code(
+            convergence::convergence(args),
+            "Error when studying convergence",
+        ),
+        Command::Compare(args) => exit_code(compare::compare(args), "Error when comparing runs"),
+        Command::Validate(args) => {
+            exit_code(validate::validate(args), "Error when validating config")
+        }
+        Command::Periodogram(args) => exit_code(
+            periodogram::periodogram(args),
+            "Error when analysing periods",
+        ),
+    }
+}
 
-    match ConfigSystem::parse(args.filename) {
+/// Logs the error of a subcommand and turns the result into the exit code of the process
+fn exit_code(result: Result<(), ApplicationError>, context: &str) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "{context}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: RunArgs) -> ExitCode {
+    let configsystem = match (args.preset, args.filename) {
+        (Some(preset), _) => Ok(preset.configsystem()),
+        (None, Some(filename)) => ConfigSystem::parse_as(filename, args.format),
+        (None, None) => unreachable!("clap requires a filename without a preset"),
+    };
+    match configsystem
+        .and_then(|configsystem| apply_overrides(configsystem, &args.overrides.overrides))
+    {
         Ok(configsystem) => {
             let system = configsystem.system;
-            let config = configsystem.config;
+            let test_particles = configsystem.test_particles;
+            let mut config = configsystem.config;
+            if args.threads.is_some() {
+                config.threads = args.threads;
+            }
+            if args.stdout.is_some() {
+                config.stdout = args.stdout;
+            }
+            if args.reverse {
+                config.reverse = true;
+            }
+            let streaming = config.stdout.is_some();
             tracing::event!(tracing::Level::DEBUG, "⚙️ Config \n{:#?}", &config);
             tracing::event!(tracing::Level::DEBUG, "🪐 System \n{:#?}", &system);
+            tracing::event!(
+                tracing::Level::DEBUG,
+                "☄️ {} test particles",
+                test_particles.len()
+            );
+
+            let monitor: Box<dyn Monitor> = if args.tui {
+                match Tui::new() {
+                    Ok(tui) => Box::new(tui),
+                    Err(e) => {
+                        tracing::event!(tracing::Level::ERROR, "Could not start terminal UI: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            } else {
+                Box::new(Progress::new())
+            };
 
             tracing::event!(tracing::Level::INFO, "Running Simulation 🚀");
-            run_simulation(config, system);
-            tracing::event!(tracing::Level::INFO, "Done 🥳");
+            let result = Simulation::builder(config, system)
+                .test_particles(test_particles)
+                .body_properties(configsystem.body_properties)
+                .boxed_monitor(monitor)
+                .build()
+                .and_then(Simulation::run);
+            let result = result.map(|(_, summary)| {
+                tracing::event!(tracing::Level::INFO, "Done 🥳");
+                // keep the stream on stdout clean
+                if streaming {
+                    eprintln!("{summary}");
+                } else {
+                    println!("{summary}");
+                }
+            });
+            exit_code(result, "Simulation failed")
         }
-        Err(e) => {
-            tracing::event!(tracing::Level::ERROR, "Error when generating config: {e}");
-        }
-    };
+        Err(e) => exit_code(Err(e), "Error when generating config"),
+    }
 }
@@ -0,0 +1,180 @@
+use kepler_core::types::System;
+
+#[cfg(feature = "parallel")]
+use crate::gravity::{
+    apply_accelerations, calculate_accelerations_parallel,
+    calculate_test_particle_accelerations_parallel,
+};
+use crate::{
+    configsystem::TestParticle,
+    gravity::{calculate_test_particle_accelerations, move_test_particles},
+};
+
+/// Advances the system by one timestep.
+///
+/// The single-threaded mover delegates to `kepler_core`, the parallel mover
+/// evaluates the accelerations of all bodies on a dedicated rayon thread pool.
+pub enum Mover {
+    Serial,
+    #[cfg(feature = "parallel")]
+    Parallel(rayon::ThreadPool),
+}
+
+impl Mover {
+    /// Creates a mover for the requested number of threads.
+    /// `None` or `Some(1)` gives the single-threaded mover.
+    pub fn new(threads: Option<usize>) -> Self {
+        match threads {
+            None | Some(1) => Mover::Serial,
+            #[cfg(feature = "parallel")]
+            Some(threads) => match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => {
+                    tracing::event!(
+                        tracing::Level::INFO,
+                        "Evaluating forces on {} threads",
+                        pool.current_num_threads()
+                    );
+                    Mover::Parallel(pool)
+                }
+                Err(e) => {
+                    tracing::event!(
+                        tracing::Level::WARN,
+                        "Could not build thread pool, running single-threaded: {e}"
+                    );
+                    Mover::Serial
+                }
+            },
+            #[cfg(not(feature = "parallel"))]
+            Some(threads) => {
+                tracing::event!(
+                    tracing::Level::WARN,
+                    "{threads} threads requested, but kepler_cli was built without the `parallel` feature. Running single-threaded."
+                );
+                Mover::Serial
+            }
+        }
+    }
+
+    pub fn timestep(&self, system: System, timestep: f64) -> System {
+        match self {
+            Mover::Serial => kepler_core::mover::system_timestep(system, timestep),
+            #[cfg(feature = "parallel")]
+            Mover::Parallel(pool) => {
+                let accelerations = pool.install(|| calculate_accelerations_parallel(&system));
+                apply_accelerations(system, &accelerations, timestep)
+            }
+        }
+    }
+
+    /// Moves the test particles by one timestep in the field of the bodies.
+    /// Call this with the system state from before its own timestep.
+    pub fn timestep_test_particles(
+        &self,
+        system: &System,
+        test_particles: &mut [TestParticle],
+        timestep: f64,
+    ) {
+        if test_particles.is_empty() {
+            return;
+        }
+        let accelerations = match self {
+            Mover::Serial => calculate_test_particle_accelerations(test_particles, system),
+            #[cfg(feature = "parallel")]
+            Mover::Parallel(pool) => pool
+                .install(|| calculate_test_particle_accelerations_parallel(test_particles, system)),
+        };
+        move_test_particles(test_particles, &accelerations, timestep);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Mover;
+    use crate::{
+        configsystem::TestParticle,
+        generate::{circular_speed, ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS},
+    };
+    use kepler_core::types::{Body, System};
+    use maths_rs::Vec2d;
+
+    /// The Sun with two planets on crossing eccentric orbits
+    fn planets() -> System {
+        let speed = circular_speed(SUN_MASS, ASTRONOMICAL_UNIT);
+        let body = |name: &str, mass: f64, x: f64, vy: f64| Body {
+            name: name.to_owned(),
+            mass,
+            position: Vec2d::new(x, 0.0),
+            velocity: Vec2d::new(0.0, vy),
+        };
+        System {
+            bodies: vec![
+                body("Sun", SUN_MASS, 0.0, 0.0),
+                body("Earth", EARTH_MASS, ASTRONOMICAL_UNIT, 0.8 * speed),
+                body(
+                    "Jupiter",
+                    318.0 * EARTH_MASS,
+                    -1.5 * ASTRONOMICAL_UNIT,
+                    -0.9 * speed,
+                ),
+            ],
+        }
+    }
+
+    fn assert_close(a: Vec2d, b: Vec2d, tolerance: f64) {
+        let difference = a - b;
+        let difference = (difference.x * difference.x + difference.y * difference.y).sqrt();
+        assert!(difference <= tolerance, "{a:?} {b:?}");
+    }
+
+    #[test]
+    pub fn test_particles_move_like_massless_bodies() {
+        let mover = Mover::Serial;
+        let timestep = 3600.0;
+        let speed = circular_speed(SUN_MASS, ASTRONOMICAL_UNIT);
+        let (position, velocity) = (
+            Vec2d::new(0.0, 1.2 * ASTRONOMICAL_UNIT),
+            Vec2d::new(-0.7 * speed, 0.1 * speed),
+        );
+        let mut with_body = planets();
+        with_body.bodies.push(Body {
+            name: "Probe".to_owned(),
+            mass: 1.0,
+            position,
+            velocity,
+        });
+        let mut system = planets();
+        let mut test_particles = vec![TestParticle { position, velocity }];
+        for _ in 0..5000 {
+            with_body = mover.timestep(with_body, timestep);
+            mover.timestep_test_particles(&system, &mut test_particles, timestep);
+            system = mover.timestep(system, timestep);
+        }
+        let probe = &with_body.bodies[3];
+        assert_close(
+            probe.position,
+            test_particles[0].position,
+            1e-9 * ASTRONOMICAL_UNIT,
+        );
+        assert_close(probe.velocity, test_particles[0].velocity, 1e-9 * speed);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    pub fn parallel_mover_matches_kepler_core() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        let (serial, parallel) = (Mover::Serial, Mover::Parallel(pool));
+        let timestep = 3600.0;
+        let (mut a, mut b) = (planets(), planets());
+        for _ in 0..5000 {
+            a = serial.timestep(a, timestep);
+            b = parallel.timestep(b, timestep);
+        }
+        for (a, b) in a.bodies.iter().zip(b.bodies.iter()) {
+            assert_close(a.position, b.position, 1e-9 * ASTRONOMICAL_UNIT);
+            assert_close(a.velocity, b.velocity, 1e-9 * 3e4);
+        }
+    }
+}
@@ -0,0 +1,661 @@
+use std::f64::consts::PI;
+
+use kepler_core::types::{Body, System};
+use maths_rs::{num::Cast, Vec2d};
+use serde::Serialize;
+
+use crate::{
+    error::ApplicationError,
+    gravity::GRAVITATIONAL_CONSTANT,
+    simulation::format_time,
+    stability::primaries,
+    trajectory::{State, Trajectories},
+};
+
+/// Frequencies per resolvable frequency interval 1/T of the periodogram
+const OVERSAMPLING: f64 = 5.0;
+
+/// Peaks reported per series
+const PEAKS: usize = 3;
+
+/// Normalised Lomb–Scargle power over frequency
+#[derive(Debug, Clone)]
+pub struct Periodogram {
+    /// Frequencies in 1/s, equally spaced
+    pub frequencies: Vec<f64>,
+    pub power: Vec<f64>,
+}
+
+/// A local maximum of a periodogram
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Peak {
+    /// Period in s, interpolated between the frequencies of the periodogram
+    pub period: f64,
+    pub power: f64,
+}
+
+/// The periodogram of one series of a body and its strongest peaks
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesPeriods {
+    pub body: String,
+    /// The body the series is relative to, `None` for the coordinates of the primary
+    pub about: Option<String>,
+    /// `x`, `y`, `semi_major_axis` or `eccentricity`
+    pub series: &'static str,
+    pub peaks: Vec<Peak>,
+    #[serde(skip)]
+    pub periodogram: Periodogram,
+}
+
+/// Two bodies whose orbital periods are close to a ratio of small integers
+#[derive(Debug, Clone, Serialize)]
+pub struct Resonance {
+    pub inner: String,
+    pub outer: String,
+    /// Orbital period of the outer body over the one of the inner body
+    pub period_ratio: f64,
+    pub p: u32,
+    pub q: u32,
+    /// Relative deviation of the period ratio from p/q
+    pub deviation: f64,
+}
+
+/// The dominant periods of the bodies of a run and the resonances between them
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodAnalysis {
+    pub primary: String,
+    pub series: Vec<SeriesPeriods>,
+    pub resonances: Vec<Resonance>,
+}
+
+/// Grid points every sample is spread onto for the FFT
+const EXTIRPOLATION_POINTS: usize = 4;
+
+/// The discrete Fourier transform Σⱼ xⱼ e^(2πi jk/n) of `re + i im` in place,
+/// the length has to be a power of two
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut length = 2;
+    while length <= n {
+        let angle = 2.0 * PI / length as f64;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + length / 2);
+                let (x, y) = (re[b] * cos - im[b] * sin, re[b] * sin + im[b] * cos);
+                (re[b], im[b]) = (re[a] - x, im[a] - y);
+                (re[a], im[a]) = (re[a] + x, im[a] + y);
+            }
+        }
+        length <<= 1;
+    }
+}
+
+/// Adds `value` at the fractional index `position` of `grid` by Lagrange
+/// interpolation backwards onto the `EXTIRPOLATION_POINTS` nearest points,
+/// so that sums of `value` times a smooth function of the position are kept
+fn extirpolate(grid: &mut [f64], position: f64, value: f64) {
+    let points = EXTIRPOLATION_POINTS;
+    if position.fract() == 0.0 {
+        grid[position as usize % grid.len()] += value;
+        return;
+    }
+    let low =
+        ((position - 0.5 * points as f64 + 1.0).floor().max(0.0) as usize).min(grid.len() - points);
+    let high = low + points - 1;
+    let product: f64 = (low..=high).map(|j| position - j as f64).product();
+    // the denominator ∏ₖ₌ₗₒᵥ..ₕᵢ₉ₕ, ₖ≠ⱼ (j - k) of the Lagrange weight of point j
+    let mut denominator: f64 = (1..points).map(|k| k as f64).product();
+    for j in (low..=high).rev() {
+        grid[j] += value * product / (denominator * (position - j as f64));
+        if j > low {
+            denominator *= (j as f64 - 1.0 - high as f64) / (j - low) as f64;
+        }
+    }
+}
+
+/// The Lomb–Scargle periodogram of `values` sampled at `times`, from one cycle
+/// over the whole span up to the Nyquist frequency of the mean sampling interval.
+///
+/// The power is the fraction of the variance explained by a sinusoid of the frequency,
+/// between 0 and 1. `None` if there are too few samples or the values do not vary.
+///
+/// The trigonometric sums are evaluated for all frequencies at once with the method
+/// of Press and Rybicki (1989): the samples are spread onto a regular grid and
+/// transformed with an FFT, which takes O(n log n) instead of O(n²) time.
+pub fn lomb_scargle(times: &[f64], values: &[f64]) -> Option<Periodogram> {
+    let count = times.len().min(values.len());
+    if count < 4 {
+        return None;
+    }
+    let (times, values) = (&times[..count], &values[..count]);
+    let mean = values.iter().sum::<f64>() / count as f64;
+    let squares = values.iter().map(|y| (y - mean).powi(2)).sum::<f64>();
+    let start = times.iter().copied().fold(f64::INFINITY, f64::min);
+    let span = times.iter().copied().fold(-f64::INFINITY, f64::max) - start;
+    if !(squares > 0.0 && span > 0.0) {
+        return None;
+    }
+
+    // frequency k / (OVERSAMPLING span) is bin k of the transform, the grid is large
+    // enough that twice the Nyquist frequency is still below its Nyquist frequency
+    let size = (2.0 * OVERSAMPLING * (count * EXTIRPOLATION_POINTS) as f64).ceil() as usize;
+    let size = size.next_power_of_two();
+    let spacing = 1.0 / (OVERSAMPLING * span);
+    // the values are spread onto the real part and the ones onto the imaginary part
+    // at twice the position, so one transform gives the sums at ω and 2ω
+    let (mut re, mut im) = (vec![0.0; size], vec![0.0; size]);
+    for (t, y) in times.iter().zip(values.iter()) {
+        let position = ((t - start) * size as f64 * spacing) % size as f64;
+        extirpolate(&mut re, position, y - mean);
+        extirpolate(&mut im, (2.0 * position) % size as f64, 1.0);
+    }
+    fft(&mut re, &mut im);
+
+    let lowest = OVERSAMPLING.round() as usize;
+    let nyquist = 0.5 * (count - 1) as f64 / span;
+    let (mut frequencies, mut power) = (vec![], vec![]);
+    for k in (lowest..).take_while(|&k| k as f64 * spacing <= nyquist) {
+        // Σ y cos ωt, Σ y sin ωt, Σ cos 2ωt and Σ sin 2ωt with t from the start,
+        // separated by the symmetry of the transforms of real sequences
+        let (re_k, im_k, re_mirror, im_mirror) = (re[k], im[k], re[size - k], im[size - k]);
+        let (yc, ys) = (0.5 * (re_k + re_mirror), 0.5 * (im_k - im_mirror));
+        let (c2, s2) = (0.5 * (im_k + im_mirror), 0.5 * (re_mirror - re_k));
+        let hypot = c2.hypot(s2);
+        // cos 2ωτ and sin 2ωτ of the offset τ that decouples the sine and cosine terms
+        let (cos_2tau, sin_2tau) = if hypot > 0.0 {
+            (c2 / hypot, s2 / hypot)
+        } else {
+            (1.0, 0.0)
+        };
+        let cos_tau = (0.5 * (1.0 + cos_2tau)).sqrt();
+        let sin_tau = (0.5 * (1.0 - cos_2tau)).sqrt().copysign(sin_2tau);
+        // Σ cos² ω(t - τ) and Σ sin² ω(t - τ)
+        let cc = 0.5 * count as f64 + 0.5 * (cos_2tau * c2 + sin_2tau * s2);
+        let ss = count as f64 - cc;
+        let cosine = cos_tau * yc + sin_tau * ys;
+        let sine = cos_tau * ys - sin_tau * yc;
+        let cosine = if cc > 0.0 { cosine * cosine / cc } else { 0.0 };
+        let sine = if ss > 0.0 { sine * sine / ss } else { 0.0 };
+        frequencies.push(k as f64 * spacing);
+        power.push((cosine + sine) / squares);
+    }
+    Some(Periodogram { frequencies, power })
+}
+
+impl Periodogram {
+    /// The `count` highest local maxima, highest first
+    pub fn peaks(&self, count: usize) -> Vec<Peak> {
+        let power = &self.power;
+        let mut peaks: Vec<Peak> = (0..power.len())
+            .filter(|&i| {
+                (i == 0 || power[i] > power[i - 1])
+                    && (i + 1 == power.len() || power[i] >= power[i + 1])
+            })
+            .map(|i| {
+                let mut frequency = self.frequencies[i];
+                if i > 0 && i + 1 < power.len() {
+                    // the vertex of the parabola through the peak and its neighbours
+                    let curvature = power[i - 1] - 2.0 * power[i] + power[i + 1];
+                    if curvature < 0.0 {
+                        let offset = 0.5 * (power[i - 1] - power[i + 1]) / curvature;
+                        frequency += offset * (self.frequencies[i + 1] - self.frequencies[i]);
+                    }
+                }
+                Peak {
+                    period: 1.0 / frequency,
+                    power: power[i],
+                }
+            })
+            .collect();
+        peaks.sort_by(|a, b| b.power.total_cmp(&a.power));
+        peaks.truncate(count);
+        peaks
+    }
+}
+
+/// Semi-major axis and eccentricity of the osculating orbit about the primary,
+/// `None` if the body is not bound to it
+fn elements(state: &State, primary: &State) -> Option<(f64, f64)> {
+    let (x, y) = (state.x - primary.x, state.y - primary.y);
+    let (vx, vy) = (state.vx - primary.vx, state.vy - primary.vy);
+    let mu = GRAVITATIONAL_CONSTANT * (state.mass + primary.mass);
+    let energy = (vx * vx + vy * vy) / 2.0 - mu / (x * x + y * y).sqrt();
+    let angular_momentum = x * vy - y * vx;
+    (energy < 0.0).then(|| {
+        let eccentricity = (1.0 + 2.0 * energy * angular_momentum * angular_momentum / (mu * mu))
+            .max(0.0)
+            .sqrt();
+        (-mu / (2.0 * energy), eccentricity)
+    })
+}
+
+fn greatest_common_divisor(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        greatest_common_divisor(b, a % b)
+    }
+}
+
+/// The ratio p/q with p, q ≤ `max_term` closest to `ratio` within `tolerance`,
+/// preferring the ones with the smallest p + q
+fn nearest_ratio(ratio: f64, max_term: u32, tolerance: f64) -> Option<(u32, u32, f64)> {
+    (1..=max_term)
+        .flat_map(|p| (1..=max_term).map(move |q| (p, q)))
+        .filter(|&(p, q)| greatest_common_divisor(p, q) == 1)
+        .map(|(p, q)| (p, q, (ratio * q as f64 / p as f64 - 1.0).abs()))
+        .filter(|&(_, _, deviation)| deviation <= tolerance)
+        .min_by(|a, b| (a.0 + a.1).cmp(&(b.0 + b.1)).then(a.2.total_cmp(&b.2)))
+}
+
+/// The state of every body at its first export
+fn initial_system(trajectories: &Trajectories) -> System {
+    let bodies = trajectories
+        .iter()
+        .filter_map(|(name, states)| {
+            let state = states.values().next()?;
+            Some(Body {
+                name: name.clone(),
+                mass: state.mass,
+                position: Vec2d::new(state.x, state.y),
+                velocity: Vec2d::new(state.vx, state.vy),
+            })
+        })
+        .collect();
+    System { bodies }
+}
+
+/// Computes the periodograms of the coordinates and orbital elements of every body
+/// about the body it orbits and looks for resonances between their orbital periods.
+///
+/// Bodies orbit the primary, the most massive body if not given, unless they start
+/// in the Hill sphere of a heavier body, like a moon in the one of its planet. The
+/// orbital period of a body is the strongest peak of its x coordinate relative to
+/// the body it orbits, and only bodies that orbit the same body are checked for
+/// resonances.
+pub fn analyse_periods(
+    trajectories: &Trajectories,
+    primary: Option<&str>,
+    tolerance: f64,
+    max_term: u32,
+) -> Result<PeriodAnalysis, ApplicationError> {
+    let initial = initial_system(trajectories);
+    let bodies = &initial.bodies;
+    let dominant = match primary {
+        Some(name) => bodies
+            .iter()
+            .position(|body| body.name == name)
+            .ok_or_else(|| ApplicationError::UnknownBodyError(name.to_owned()))?,
+        None => bodies
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.mass.total_cmp(&b.mass))
+            .map(|(index, _)| index)
+            .ok_or_else(|| {
+                ApplicationError::TrajectoryError("there are no bodies to analyse".to_owned())
+            })?,
+    };
+    let orbited = primaries(&initial, dominant);
+
+    let mut series = vec![];
+    let mut orbital_periods = vec![];
+    for (index, body) in bodies.iter().enumerate() {
+        let name = &body.name;
+        let states = &trajectories[name];
+        let Some(about) = orbited[index].map(|other| &bodies[other].name) else {
+            let times: Vec<f64> = states.values().map(|state| state.time).collect();
+            let xs: Vec<f64> = states.values().map(|state| state.x).collect();
+            let ys: Vec<f64> = states.values().map(|state| state.y).collect();
+            for (label, values) in [("x", xs), ("y", ys)] {
+                if let Some(periodogram) = lomb_scargle(&times, &values) {
+                    series.push(SeriesPeriods {
+                        body: name.clone(),
+                        about: None,
+                        series: label,
+                        peaks: periodogram.peaks(PEAKS),
+                        periodogram,
+                    });
+                }
+            }
+            continue;
+        };
+        let about_states = &trajectories[about];
+
+        let (mut times, mut xs, mut ys) = (vec![], vec![], vec![]);
+        // the elements are left out where the body is not bound
+        let (mut bound_times, mut semi_major_axes, mut eccentricities) = (vec![], vec![], vec![]);
+        for (step, state) in states.iter() {
+            let Some(primary) = about_states.get(step) else {
+                continue;
+            };
+            times.push(state.time);
+            xs.push(state.x - primary.x);
+            ys.push(state.y - primary.y);
+            if let Some((semi_major_axis, eccentricity)) = elements(state, primary) {
+                bound_times.push(state.time);
+                semi_major_axes.push(semi_major_axis);
+                eccentricities.push(eccentricity);
+            }
+        }
+
+        for (label, times, values) in [
+            ("x", &times, &xs),
+            ("y", &times, &ys),
+            ("semi_major_axis", &bound_times, &semi_major_axes),
+            ("eccentricity", &bound_times, &eccentricities),
+        ] {
+            let Some(periodogram) = lomb_scargle(times, values) else {
+                continue;
+            };
+            let peaks = periodogram.peaks(PEAKS);
+            if label == "x" {
+                if let Some(peak) = peaks.first() {
+                    orbital_periods.push((name, about, peak.period));
+                }
+            }
+            series.push(SeriesPeriods {
+                body: name.clone(),
+                about: Some(about.clone()),
+                series: label,
+                peaks,
+                periodogram,
+            });
+        }
+    }
+
+    orbital_periods.sort_by(|a, b| a.2.total_cmp(&b.2));
+    let mut resonances = vec![];
+    for (index, (inner, about, inner_period)) in orbital_periods.iter().enumerate() {
+        let siblings = orbital_periods
+            .iter()
+            .skip(index + 1)
+            .filter(|(_, other_about, _)| other_about == about);
+        for (outer, _, outer_period) in siblings {
+            let period_ratio = outer_period / inner_period;
+            if let Some((p, q, deviation)) = nearest_ratio(period_ratio, max_term, tolerance) {
+                resonances.push(Resonance {
+                    inner: (*inner).clone(),
+                    outer: (*outer).clone(),
+                    period_ratio,
+                    p,
+                    q,
+                    deviation,
+                });
+            }
+        }
+    }
+
+    Ok(PeriodAnalysis {
+        primary: bodies[dominant].name.clone(),
+        series,
+        resonances,
+    })
+}
+
+impl std::fmt::Display for PeriodAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Orbits about {}", self.primary)?;
+        // satellites are marked with the body they orbit
+        let label = |series: &SeriesPeriods| match &series.about {
+            Some(about) if *about != self.primary => {
+                format!("{} {} about {about}", series.body, series.series)
+            }
+            _ => format!("{} {}", series.body, series.series),
+        };
+        let width = self
+            .series
+            .iter()
+            .map(|series| label(series).chars().count())
+            .max()
+            .unwrap_or(0);
+        writeln!(f, "{:<width$}  Strongest periods (normalised power)", "")?;
+        for series in self.series.iter() {
+            let peaks: Vec<String> = series
+                .peaks
+                .iter()
+                .map(|peak| format!("{} ({:.2})", format_time(peak.period.as_u64()), peak.power))
+                .collect();
+            writeln!(f, "{:<width$}  {}", label(series), peaks.join(", "))?;
+        }
+        if self.resonances.is_empty() {
+            writeln!(f, "No mean-motion resonances found")?;
+        }
+        for resonance in self.resonances.iter() {
+            writeln!(
+                f,
+                "{} – {}: period ratio {:.4}, near {}:{} ({:.2} %)",
+                resonance.inner,
+                resonance.outer,
+                resonance.period_ratio,
+                resonance.p,
+                resonance.q,
+                resonance.deviation * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeMap, f64::consts::PI};
+
+    use super::{analyse_periods, lomb_scargle};
+    use crate::{
+        generate::{circular_speed, orbital_period, ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS},
+        trajectory::State,
+    };
+
+    #[test]
+    pub fn finds_the_orbital_periods_and_a_two_to_one_resonance() {
+        let inner_radius = ASTRONOMICAL_UNIT;
+        let outer_radius = ASTRONOMICAL_UNIT * 4.0_f64.cbrt();
+        let period = orbital_period(SUN_MASS, inner_radius);
+        let circular = |radius: f64, step: i64| {
+            let time = step as f64 * period / 50.0;
+            let angle = 2.0 * PI * time / orbital_period(SUN_MASS, radius);
+            let speed = circular_speed(SUN_MASS, radius);
+            (
+                step,
+                State {
+                    step,
+                    time,
+                    mass: EARTH_MASS,
+                    x: radius * angle.cos(),
+                    y: radius * angle.sin(),
+                    vx: -speed * angle.sin(),
+                    vy: speed * angle.cos(),
+                },
+            )
+        };
+        let trajectories = BTreeMap::from([
+            (
+                "Sun".to_owned(),
+                (0..1000)
+                    .map(|step| {
+                        let (step, state) = circular(inner_radius, step);
+                        let state = State {
+                            mass: SUN_MASS,
+                            x: 0.0,
+                            y: 0.0,
+                            vx: 0.0,
+                            vy: 0.0,
+                            ..state
+                        };
+                        (step, state)
+                    })
+                    .collect(),
+            ),
+            (
+                "Inner".to_owned(),
+                (0..1000).map(|step| circular(inner_radius, step)).collect(),
+            ),
+            (
+                "Outer".to_owned(),
+                (0..1000).map(|step| circular(outer_radius, step)).collect(),
+            ),
+        ]);
+
+        let analysis = analyse_periods(&trajectories, None, 0.01, 6).unwrap();
+        assert_eq!(analysis.primary, "Sun");
+        let inner = analysis
+            .series
+            .iter()
+            .find(|series| series.body == "Inner" && series.series == "x")
+            .unwrap();
+        assert!(
+            (inner.peaks[0].period / period - 1.0).abs() < 1e-3,
+            "{analysis}"
+        );
+        assert_eq!(analysis.resonances.len(), 1, "{analysis}");
+        let resonance = &analysis.resonances[0];
+        assert_eq!(
+            (resonance.inner.as_str(), resonance.outer.as_str()),
+            ("Inner", "Outer")
+        );
+        assert_eq!((resonance.p, resonance.q), (2, 1));
+    }
+
+    /// The normalised Lomb–Scargle power at `frequency` from the sums over all samples
+    fn direct_power(times: &[f64], values: &[f64], frequency: f64) -> f64 {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let squares: f64 = values.iter().map(|y| (y - mean).powi(2)).sum();
+        let omega = 2.0 * PI * frequency;
+        let (sin, cos) = times.iter().fold((0.0, 0.0), |(sin, cos), t| {
+            let (s, c) = (2.0 * omega * t).sin_cos();
+            (sin + s, cos + c)
+        });
+        let tau = f64::atan2(sin, cos) / (2.0 * omega);
+        let (mut yc, mut cc, mut ys, mut ss) = (0.0, 0.0, 0.0, 0.0);
+        for (t, y) in times.iter().zip(values.iter()) {
+            let (s, c) = (omega * (t - tau)).sin_cos();
+            yc += (y - mean) * c;
+            cc += c * c;
+            ys += (y - mean) * s;
+            ss += s * s;
+        }
+        (yc * yc / cc + ys * ys / ss) / squares
+    }
+
+    #[test]
+    pub fn fast_periodogram_matches_the_direct_sums() {
+        // unevenly sampled, with two periods and a trend
+        let times: Vec<f64> = (0..500)
+            .map(|i| i as f64 + 0.4 * (i as f64 * 1.7).sin())
+            .collect();
+        let values: Vec<f64> = times
+            .iter()
+            .map(|t| (2.0 * PI * t / 37.0).sin() + 0.5 * (2.0 * PI * t / 9.3).cos() + 1e-3 * t)
+            .collect();
+
+        let periodogram = lomb_scargle(&times, &values).unwrap();
+        assert!((periodogram.frequencies[0] * (times[499] - times[0]) - 1.0).abs() < 1e-9);
+        assert!(*periodogram.frequencies.last().unwrap() <= 0.5 * 499.0 / (times[499] - times[0]));
+        for (frequency, power) in periodogram.frequencies.iter().zip(periodogram.power.iter()) {
+            let direct = direct_power(&times, &values, *frequency);
+            assert!(
+                (power - direct).abs() < 1e-3,
+                "{frequency}: {power} {direct}"
+            );
+        }
+        let peaks = periodogram.peaks(2);
+        assert!((peaks[0].period / 37.0 - 1.0).abs() < 1e-2, "{peaks:?}");
+        assert!((peaks[1].period / 9.3 - 1.0).abs() < 1e-2, "{peaks:?}");
+    }
+
+    #[test]
+    pub fn finds_the_period_of_a_moon_about_its_planet() {
+        const MOON_MASS: f64 = 7.342e22;
+        const MOON_DISTANCE: f64 = 3.844e8;
+        let year = orbital_period(SUN_MASS, ASTRONOMICAL_UNIT);
+        let month = orbital_period(EARTH_MASS, MOON_DISTANCE);
+        // (position, velocity) on a circular orbit
+        let circular = |central_mass: f64, radius: f64, time: f64| {
+            let angle = 2.0 * PI * time / orbital_period(central_mass, radius);
+            let speed = circular_speed(central_mass, radius);
+            let (sin, cos) = angle.sin_cos();
+            ([radius * cos, radius * sin], [-speed * sin, speed * cos])
+        };
+        let state = |step: i64, mass: f64, (position, velocity): ([f64; 2], [f64; 2])| {
+            let state = State {
+                step,
+                time: step as f64 * 43_200.0,
+                mass,
+                x: position[0],
+                y: position[1],
+                vx: velocity[0],
+                vy: velocity[1],
+            };
+            (step, state)
+        };
+        let steps = 0..(2.0 * year / 43_200.0) as i64;
+        let earth = |step: i64| circular(SUN_MASS, ASTRONOMICAL_UNIT, step as f64 * 43_200.0);
+        let moon = |step: i64| {
+            let (earth_position, earth_velocity) = earth(step);
+            let (position, velocity) = circular(EARTH_MASS, MOON_DISTANCE, step as f64 * 43_200.0);
+            (
+                [
+                    earth_position[0] + position[0],
+                    earth_position[1] + position[1],
+                ],
+                [
+                    earth_velocity[0] + velocity[0],
+                    earth_velocity[1] + velocity[1],
+                ],
+            )
+        };
+        let trajectories = BTreeMap::from([
+            (
+                "Sun".to_owned(),
+                steps
+                    .clone()
+                    .map(|step| state(step, SUN_MASS, ([0.0; 2], [0.0; 2])))
+                    .collect(),
+            ),
+            (
+                "Earth".to_owned(),
+                steps
+                    .clone()
+                    .map(|step| state(step, EARTH_MASS, earth(step)))
+                    .collect(),
+            ),
+            (
+                "Moon".to_owned(),
+                steps
+                    .map(|step| state(step, MOON_MASS, moon(step)))
+                    .collect(),
+            ),
+        ]);
+
+        let analysis = analyse_periods(&trajectories, None, 0.01, 6).unwrap();
+        let period = |body: &str| {
+            let series = analysis
+                .series
+                .iter()
+                .find(|series| series.body == body && series.series == "x")
+                .unwrap();
+            (series.about.as_deref(), series.peaks[0].period)
+        };
+        let (about, earth_period) = period("Earth");
+        assert_eq!(about, Some("Sun"));
+        assert!((earth_period / year - 1.0).abs() < 1e-2, "{analysis}");
+        let (about, moon_period) = period("Moon");
+        assert_eq!(about, Some("Earth"));
+        assert!((moon_period / month - 1.0).abs() < 1e-2, "{analysis}");
+        assert!(analysis.resonances.is_empty(), "{analysis}");
+    }
+}
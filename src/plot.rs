@@ -1,4 +1,4 @@
-use crate::configsystem::Config;
+use crate::{configsystem::Config, error::ApplicationError};
 use maths_rs::min;
 use plotters::{prelude::*, style::text_anchor::Pos};
 use std::{fs::DirBuilder, path::Path};
@@ -19,10 +19,7 @@ pub struct PlotDatum {
     pub potential_energy: Option<f64>,
 }
 
-pub fn plot_total_energy(
-    data: Vec<PlotDatum>,
-    config: &Config,
-) -> Result<(), DrawingAreaErrorKind<std::io::Error>> {
+pub fn plot_total_energy(data: Vec<PlotDatum>, config: &Config) -> Result<(), ApplicationError> {
     let path = Path::new(&config.export_directory);
 
     if !path.exists() {
@@ -191,3 +188,211 @@ pub fn plot_total_energy(
         .draw()?;
     Ok(())
 }
+
+/// One series of a plot with a logarithmic axis
+pub struct LogSeries<'a> {
+    pub label: &'a str,
+    pub points: Vec<(f64, f64)>,
+}
+
+/// Plots errors against the timestep on log–log axes into `<prefix>_convergence.svg`,
+/// leaving out points that cannot be shown on a log scale
+pub fn plot_convergence(series: &[LogSeries], config: &Config) -> Result<(), ApplicationError> {
+    let path = Path::new(&config.export_directory);
+
+    if !path.exists() {
+        DirBuilder::new()
+            .recursive(true)
+            .create(path)
+            .expect("That the export path could be created.");
+    }
+
+    let filename = format! {"{}_convergence.svg", config.export_file_name_prefix};
+    let fullpath = path.join(filename);
+
+    let root_drawing_area = SVGBackend::new(&fullpath, (640, 480)).into_drawing_area();
+    root_drawing_area
+        .fill(&WHITE)
+        .expect("Should be able to fill the drawing area with white");
+    let root_drawing_area = root_drawing_area.margin(20, 20, 20, 40);
+
+    let shown = |(x, y): &(f64, f64)| *x > 0.0 && *y > 0.0 && y.is_finite();
+    let points = || {
+        series
+            .iter()
+            .flat_map(|s| s.points.iter().filter(|p| shown(p)))
+    };
+    let bounds = |values: Vec<f64>| {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(-f64::INFINITY, f64::max);
+        if min.is_finite() && max.is_finite() {
+            // half a decade of padding
+            (min / 3.0, max * 3.0)
+        } else {
+            (0.1, 10.0)
+        }
+    };
+    let (x_min, x_max) = bounds(points().map(|(x, _)| *x).collect());
+    let (y_min, y_max) = bounds(points().map(|(_, y)| *y).collect());
+
+    let mut chart_context = ChartBuilder::on(&root_drawing_area)
+        .caption("Error over Timestep", ("Sans-serif", 20).into_font())
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d((x_min..x_max).log_scale(), (y_min..y_max).log_scale())?;
+
+    chart_context
+        .configure_mesh()
+        .x_label_formatter(&format_label)
+        .y_label_formatter(&format_label)
+        .x_desc("Timestep / s")
+        .y_desc("Error")
+        .draw()?;
+
+    for (index, series) in series.iter().enumerate() {
+        let color = Palette99::pick(index).to_rgba();
+        let points: Vec<(f64, f64)> = series.points.iter().copied().filter(shown).collect();
+        chart_context.draw_series(
+            points
+                .iter()
+                .map(|point| Circle::new(*point, 3, color.filled())),
+        )?;
+        chart_context
+            .draw_series(LineSeries::new(points, color))?
+            .label(series.label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    chart_context
+        .configure_series_labels()
+        .position(SeriesLabelPosition::LowerRight)
+        .border_style(BLACK)
+        .background_style(WHITE)
+        .draw()?;
+    Ok(())
+}
+
+/// Plots the divergence of two runs over time with a logarithmic y axis into `fullpath`,
+/// leaving out points without divergence
+pub fn plot_divergence(
+    series: &[LogSeries],
+    fullpath: &Path,
+    y_desc: &str,
+) -> Result<(), ApplicationError> {
+    let root_drawing_area = SVGBackend::new(fullpath, (640, 480)).into_drawing_area();
+    root_drawing_area
+        .fill(&WHITE)
+        .expect("Should be able to fill the drawing area with white");
+    let root_drawing_area = root_drawing_area.margin(20, 20, 20, 40);
+
+    let shown = |(_, y): &(f64, f64)| *y > 0.0 && y.is_finite();
+    let points = || {
+        series
+            .iter()
+            .flat_map(|s| s.points.iter().filter(|p| shown(p)))
+    };
+    let x_min = points().map(|(x, _)| *x).fold(f64::INFINITY, f64::min);
+    let x_max = points().map(|(x, _)| *x).fold(-f64::INFINITY, f64::max);
+    let y_min = points().map(|(_, y)| *y).fold(f64::INFINITY, f64::min);
+    let y_max = points().map(|(_, y)| *y).fold(-f64::INFINITY, f64::max);
+    let (x_min, x_max) = match x_min < x_max {
+        true => (x_min, x_max),
+        false if x_min.is_finite() => (x_min - 1.0, x_min + 1.0),
+        false => (0.0, 1.0),
+    };
+    let (y_min, y_max) = match y_min.is_finite() {
+        true => (y_min / 3.0, y_max * 3.0),
+        false => (0.1, 10.0),
+    };
+
+    let mut chart_context = ChartBuilder::on(&root_drawing_area)
+        .caption("Divergence over Time", ("Sans-serif", 20).into_font())
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d(x_min..x_max, (y_min..y_max).log_scale())?;
+
+    chart_context
+        .configure_mesh()
+        .x_labels(6)
+        .x_label_formatter(&format_label)
+        .y_label_formatter(&format_label)
+        .x_desc("Time / s")
+        .y_desc(y_desc)
+        .draw()?;
+
+    for (index, series) in series.iter().enumerate() {
+        let color = Palette99::pick(index).to_rgba();
+        let points: Vec<(f64, f64)> = series.points.iter().copied().filter(shown).collect();
+        chart_context
+            .draw_series(LineSeries::new(points, color))?
+            .label(series.label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    chart_context
+        .configure_series_labels()
+        .position(SeriesLabelPosition::LowerRight)
+        .border_style(BLACK)
+        .background_style(WHITE)
+        .draw()?;
+    Ok(())
+}
+
+/// Plots periodograms over the period with a logarithmic x axis into `fullpath`
+pub fn plot_periodogram(series: &[LogSeries], fullpath: &Path) -> Result<(), ApplicationError> {
+    let root_drawing_area = SVGBackend::new(fullpath, (640, 480)).into_drawing_area();
+    root_drawing_area
+        .fill(&WHITE)
+        .expect("Should be able to fill the drawing area with white");
+    let root_drawing_area = root_drawing_area.margin(20, 20, 20, 40);
+
+    let shown = |(x, y): &(f64, f64)| *x > 0.0 && x.is_finite() && y.is_finite();
+    let points = || {
+        series
+            .iter()
+            .flat_map(|s| s.points.iter().filter(|p| shown(p)))
+    };
+    let x_min = points().map(|(x, _)| *x).fold(f64::INFINITY, f64::min);
+    let x_max = points().map(|(x, _)| *x).fold(-f64::INFINITY, f64::max);
+    let y_max = points().map(|(_, y)| *y).fold(0.0, f64::max);
+    let (x_min, x_max) = match x_min < x_max {
+        true => (x_min, x_max),
+        false if x_min.is_finite() => (x_min / 2.0, x_min * 2.0),
+        false => (0.1, 10.0),
+    };
+    let y_max = match y_max > 0.0 {
+        true => y_max * 1.1,
+        false => 1.0,
+    };
+
+    let mut chart_context = ChartBuilder::on(&root_drawing_area)
+        .caption("Periodogram", ("Sans-serif", 20).into_font())
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d((x_min..x_max).log_scale(), 0.0..y_max)?;
+
+    chart_context
+        .configure_mesh()
+        .x_label_formatter(&format_label)
+        .y_label_formatter(&format_label)
+        .x_desc("Period / s")
+        .y_desc("Normalised power")
+        .draw()?;
+
+    for (index, series) in series.iter().enumerate() {
+        let color = Palette99::pick(index).to_rgba();
+        let points: Vec<(f64, f64)> = series.points.iter().copied().filter(shown).collect();
+        chart_context
+            .draw_series(LineSeries::new(points, color))?
+            .label(series.label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    chart_context
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .border_style(BLACK)
+        .background_style(WHITE)
+        .draw()?;
+    Ok(())
+}
@@ -0,0 +1,393 @@
+use std::{collections::BTreeMap, f64::consts::PI};
+
+use clap::ValueEnum;
+use kepler_core::types::{Body, System};
+use maths_rs::Vec2d;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    configsystem::{ConfigSystem, TestParticle},
+    frame::Frame,
+    generate::{
+        circular_speed, config_for_periods, generate_figure_eight, orbital_period, polar,
+        ASTRONOMICAL_UNIT, EARTH_MASS, JUPITER_MASS, SUN_MASS,
+    },
+    gravity::GRAVITATIONAL_CONSTANT,
+};
+
+/// Ready-made systems for demos and regression tests
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// The Sun with Mercury, Venus, Earth and Mars at J2000
+    InnerSolarSystem,
+    /// The Sun with Jupiter, Saturn, Uranus and Neptune at J2000
+    OuterSolarSystem,
+    /// Earth and Moon on the Moon's eccentric orbit
+    EarthMoon,
+    /// Sun and Jupiter with Trojan test particles around L4 and L5
+    SunJupiterTrojans,
+    /// The binary Alpha Centauri A and B, without the distant Proxima
+    AlphaCentauri,
+    /// Pluto and its moon Charon
+    PlutoCharon,
+    /// Three equal masses on a rotating equilateral triangle
+    LagrangeTriangle,
+    /// The figure-eight choreography of three equal masses
+    FigureEight,
+}
+
+/// Mean orbital elements of a planet, angles in degrees
+struct Elements {
+    name: &'static str,
+    mass: f64,
+    /// in AU
+    semi_major_axis: f64,
+    eccentricity: f64,
+    inclination: f64,
+    mean_longitude: f64,
+    longitude_of_perihelion: f64,
+    longitude_of_ascending_node: f64,
+}
+
+/// The mean elements at J2000 from Standish, "Keplerian Elements for Approximate
+/// Positions of the Major Planets" (JPL), with the Earth–Moon barycenter as Earth
+const PLANETS: [Elements; 8] = [
+    Elements {
+        name: "Mercury",
+        mass: 3.3011e23,
+        semi_major_axis: 0.38709927,
+        eccentricity: 0.20563593,
+        inclination: 7.00497902,
+        mean_longitude: 252.25032350,
+        longitude_of_perihelion: 77.45779628,
+        longitude_of_ascending_node: 48.33076593,
+    },
+    Elements {
+        name: "Venus",
+        mass: 4.8675e24,
+        semi_major_axis: 0.72333566,
+        eccentricity: 0.00677672,
+        inclination: 3.39467605,
+        mean_longitude: 181.97909950,
+        longitude_of_perihelion: 131.60246718,
+        longitude_of_ascending_node: 76.67984255,
+    },
+    Elements {
+        name: "Earth",
+        mass: EARTH_MASS + MOON_MASS,
+        semi_major_axis: 1.00000261,
+        eccentricity: 0.01671123,
+        inclination: -0.00001531,
+        mean_longitude: 100.46457166,
+        longitude_of_perihelion: 102.93768193,
+        longitude_of_ascending_node: 0.0,
+    },
+    Elements {
+        name: "Mars",
+        mass: 6.4171e23,
+        semi_major_axis: 1.52371034,
+        eccentricity: 0.09339410,
+        inclination: 1.84969142,
+        mean_longitude: -4.55343205,
+        longitude_of_perihelion: -23.94362959,
+        longitude_of_ascending_node: 49.55953891,
+    },
+    Elements {
+        name: "Jupiter",
+        mass: JUPITER_MASS,
+        semi_major_axis: 5.20288700,
+        eccentricity: 0.04838624,
+        inclination: 1.30439695,
+        mean_longitude: 34.39644051,
+        longitude_of_perihelion: 14.72847983,
+        longitude_of_ascending_node: 100.47390909,
+    },
+    Elements {
+        name: "Saturn",
+        mass: 5.6834e26,
+        semi_major_axis: 9.53667594,
+        eccentricity: 0.05386179,
+        inclination: 2.48599187,
+        mean_longitude: 49.95424423,
+        longitude_of_perihelion: 92.59887831,
+        longitude_of_ascending_node: 113.66242448,
+    },
+    Elements {
+        name: "Uranus",
+        mass: 8.6810e25,
+        semi_major_axis: 19.18916464,
+        eccentricity: 0.04725744,
+        inclination: 0.77263783,
+        mean_longitude: 313.23810451,
+        longitude_of_perihelion: 170.95427630,
+        longitude_of_ascending_node: 74.01692503,
+    },
+    Elements {
+        name: "Neptune",
+        mass: 1.02413e26,
+        semi_major_axis: 30.06992276,
+        eccentricity: 0.00859048,
+        inclination: 1.77004347,
+        mean_longitude: -55.12002969,
+        longitude_of_perihelion: 44.96476227,
+        longitude_of_ascending_node: 131.78422574,
+    },
+];
+
+const MOON_MASS: f64 = 7.342e22;
+
+/// Solves Kepler's equation `M = E - e sin E` for the eccentric anomaly
+fn eccentric_anomaly(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    let mut anomaly = mean_anomaly;
+    for _ in 0..50 {
+        let correction = (anomaly - eccentricity * anomaly.sin() - mean_anomaly)
+            / (1.0 - eccentricity * anomaly.cos());
+        anomaly -= correction;
+        if correction.abs() < 1e-14 {
+            break;
+        }
+    }
+    anomaly
+}
+
+/// The heliocentric position and velocity of a planet, projected onto the ecliptic
+fn planet(elements: &Elements) -> Body {
+    let mu = GRAVITATIONAL_CONSTANT * (SUN_MASS + elements.mass);
+    let a = elements.semi_major_axis * ASTRONOMICAL_UNIT;
+    let e = elements.eccentricity;
+    let inclination = elements.inclination.to_radians();
+    let node = elements.longitude_of_ascending_node.to_radians();
+    let argument_of_perihelion =
+        (elements.longitude_of_perihelion - elements.longitude_of_ascending_node).to_radians();
+    let mean_anomaly = (elements.mean_longitude - elements.longitude_of_perihelion)
+        .to_radians()
+        .rem_euclid(2.0 * PI);
+
+    // position and velocity in the orbital plane, x towards the perihelion
+    let anomaly = eccentric_anomaly(mean_anomaly, e);
+    let anomaly_rate = (mu / a.powi(3)).sqrt() / (1.0 - e * anomaly.cos());
+    let minor = (1.0 - e * e).sqrt();
+    let position = (a * (anomaly.cos() - e), a * minor * anomaly.sin());
+    let velocity = (
+        -a * anomaly.sin() * anomaly_rate,
+        a * minor * anomaly.cos() * anomaly_rate,
+    );
+
+    // rotate into the ecliptic and drop the component out of it
+    let (sin_w, cos_w) = argument_of_perihelion.sin_cos();
+    let (sin_o, cos_o) = node.sin_cos();
+    let cos_i = inclination.cos();
+    let rotate = |(x, y): (f64, f64)| {
+        Vec2d::new(
+            (cos_w * cos_o - sin_w * sin_o * cos_i) * x
+                + (-sin_w * cos_o - cos_w * sin_o * cos_i) * y,
+            (cos_w * sin_o + sin_w * cos_o * cos_i) * x
+                + (-sin_w * sin_o + cos_w * cos_o * cos_i) * y,
+        )
+    };
+    Body {
+        name: elements.name.to_owned(),
+        mass: elements.mass,
+        position: rotate(position),
+        velocity: rotate(velocity),
+    }
+}
+
+fn solar_system(planets: &[Elements]) -> ConfigSystem {
+    let sun = Body {
+        name: "Sun".to_owned(),
+        mass: SUN_MASS,
+        position: Vec2d::new(0.0, 0.0),
+        velocity: Vec2d::new(0.0, 0.0),
+    };
+    let bodies = std::iter::once(sun)
+        .chain(planets.iter().map(planet))
+        .collect();
+    let periods: Vec<f64> = planets
+        .iter()
+        .map(|planet| orbital_period(SUN_MASS, planet.semi_major_axis * ASTRONOMICAL_UNIT))
+        .collect();
+    let mut config = config_for_periods(periods[0], periods[periods.len() - 1]);
+    config.frame = Frame::Barycentric;
+    ConfigSystem {
+        config,
+        system: System { bodies },
+        test_particles: vec![],
+        body_properties: BTreeMap::new(),
+    }
+}
+
+/// Two bodies at the periapsis of their orbit around the barycenter in the origin
+fn binary(
+    (primary, primary_mass): (&str, f64),
+    (secondary, secondary_mass): (&str, f64),
+    semi_major_axis: f64,
+    eccentricity: f64,
+) -> ConfigSystem {
+    let total_mass = primary_mass + secondary_mass;
+    let separation = semi_major_axis * (1.0 - eccentricity);
+    let speed = (GRAVITATIONAL_CONSTANT * total_mass * (1.0 + eccentricity) / separation).sqrt();
+    let primary_share = secondary_mass / total_mass;
+    let secondary_share = primary_mass / total_mass;
+    let bodies = vec![
+        Body {
+            name: primary.to_owned(),
+            mass: primary_mass,
+            position: Vec2d::new(-separation * primary_share, 0.0),
+            velocity: Vec2d::new(0.0, -speed * primary_share),
+        },
+        Body {
+            name: secondary.to_owned(),
+            mass: secondary_mass,
+            position: Vec2d::new(separation * secondary_share, 0.0),
+            velocity: Vec2d::new(0.0, speed * secondary_share),
+        },
+    ];
+
+    // the bodies are fastest at periapsis, so the steps are chosen for the time spent there
+    let period = orbital_period(total_mass, semi_major_axis);
+    ConfigSystem {
+        config: config_for_periods(period * (1.0 - eccentricity).powf(1.5), period),
+        system: System { bodies },
+        test_particles: vec![],
+        body_properties: BTreeMap::new(),
+    }
+}
+
+/// Test particles spread around L4 and L5, co-rotating with Jupiter
+fn sun_jupiter_trojans() -> ConfigSystem {
+    const TROJANS_PER_POINT: usize = 21;
+    const SPREAD: f64 = 15.0;
+
+    let mut configsystem = binary(
+        ("Sun", SUN_MASS),
+        ("Jupiter", JUPITER_MASS),
+        5.2044 * ASTRONOMICAL_UNIT,
+        0.0,
+    );
+    let [sun, jupiter] = [0, 1].map(|index| configsystem.system.bodies[index].position);
+    let separation = jupiter.x - sun.x;
+    let angular_velocity = configsystem.system.bodies[1].velocity.y / jupiter.x;
+
+    configsystem.test_particles = [60.0, -60.0]
+        .into_iter()
+        .flat_map(|point: f64| {
+            (0..TROJANS_PER_POINT).map(move |index| {
+                let offset = SPREAD * (2.0 * index as f64 / (TROJANS_PER_POINT - 1) as f64 - 1.0);
+                let position = sun + polar(separation, (point + offset).to_radians());
+                TestParticle {
+                    position,
+                    velocity: Vec2d::new(-position.y, position.x) * angular_velocity,
+                }
+            })
+        })
+        .collect();
+
+    // the Trojans librate around L4 and L5 in about 150 years
+    let period = orbital_period(SUN_MASS + JUPITER_MASS, separation);
+    configsystem.config = config_for_periods(period, 15.0 * period);
+    configsystem.config.export_test_particle_history = true;
+    configsystem
+}
+
+fn lagrange_triangle() -> ConfigSystem {
+    let mass = SUN_MASS;
+    let side = ASTRONOMICAL_UNIT;
+    let radius = side / 3.0_f64.sqrt();
+    // the pull of the two other bodies gives the speed of a circular orbit
+    // with a radius of one side around a single body
+    let speed = circular_speed(mass, side);
+    let bodies = (0..3)
+        .map(|index| {
+            let angle = PI / 2.0 + 2.0 * PI * index as f64 / 3.0;
+            Body {
+                name: format!("Body {}", index + 1),
+                mass,
+                position: polar(radius, angle),
+                velocity: polar(speed, angle + PI / 2.0),
+            }
+        })
+        .collect();
+
+    // equal masses make the triangle unstable, after a few periods it breaks up
+    let period = 2.0 * PI * radius / speed;
+    ConfigSystem {
+        config: config_for_periods(period, 5.0 * period),
+        system: System { bodies },
+        test_particles: vec![],
+        body_properties: BTreeMap::new(),
+    }
+}
+
+impl Preset {
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .expect("that no preset is skipped")
+            .get_name()
+            .to_owned()
+    }
+
+    pub fn configsystem(self) -> ConfigSystem {
+        match self {
+            Preset::InnerSolarSystem => solar_system(&PLANETS[..4]),
+            Preset::OuterSolarSystem => solar_system(&PLANETS[4..]),
+            Preset::EarthMoon => {
+                binary(("Earth", EARTH_MASS), ("Moon", MOON_MASS), 3.844e8, 0.0549)
+            }
+            Preset::SunJupiterTrojans => sun_jupiter_trojans(),
+            Preset::AlphaCentauri => binary(
+                ("Alpha Centauri A", 1.0788 * SUN_MASS),
+                ("Alpha Centauri B", 0.9092 * SUN_MASS),
+                23.3 * ASTRONOMICAL_UNIT,
+                0.5208,
+            ),
+            Preset::PlutoCharon => binary(("Pluto", 1.303e22), ("Charon", 1.586e21), 1.9596e7, 0.0),
+            Preset::LagrangeTriangle => lagrange_triangle(),
+            Preset::FigureEight => generate_figure_eight(
+                &mut ChaCha8Rng::seed_from_u64(0),
+                SUN_MASS,
+                ASTRONOMICAL_UNIT,
+                0.0,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::ValueEnum;
+
+    use super::Preset;
+    use crate::generate::ASTRONOMICAL_UNIT;
+
+    #[test]
+    pub fn presets_place_bodies_where_expected() {
+        for preset in Preset::value_variants() {
+            let configsystem = preset.configsystem();
+            assert!(!configsystem.system.bodies.is_empty(), "{preset:?}");
+            assert!(configsystem.config.steps > 0, "{preset:?}");
+        }
+
+        // Earth was just past perihelion at J2000
+        let inner = Preset::InnerSolarSystem.configsystem();
+        let earth = &inner.system.bodies[3];
+        assert_eq!(earth.name, "Earth");
+        let distance = earth.position.x.hypot(earth.position.y) / ASTRONOMICAL_UNIT;
+        assert!((distance - 0.9833).abs() < 1e-3, "{distance}");
+        let speed = earth.velocity.x.hypot(earth.velocity.y);
+        assert!((speed - 30290.0).abs() < 30.0, "{speed}");
+
+        // the middle Trojan of each group forms an equilateral triangle with Sun and Jupiter
+        let trojans = Preset::SunJupiterTrojans.configsystem();
+        let [sun, jupiter] = [0, 1].map(|index| trojans.system.bodies[index].position);
+        let separation = jupiter.x - sun.x;
+        for index in [10, 31] {
+            let trojan = trojans.test_particles[index].position;
+            for body in [sun, jupiter] {
+                let distance = (trojan.x - body.x).hypot(trojan.y - body.y);
+                assert!((distance / separation - 1.0).abs() < 1e-12, "{index}");
+            }
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use numpy::{PyArray1, PyArray2, PyArray3, PyArrayMethods};
+use pyo3::{
+    exceptions::{PyRuntimeError, PyTypeError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyDict, PyList},
+};
+
+use crate::{
+    configsystem::ConfigSystem,
+    configvalue::{get_value, set_value},
+    error::ApplicationError,
+    simulation::Simulation,
+    summary::RunSummary,
+};
+
+impl From<ApplicationError> for PyErr {
+    fn from(value: ApplicationError) -> Self {
+        PyValueError::new_err(value.to_string())
+    }
+}
+
+fn json_to_py<'py>(py: Python<'py>, value: &serde_json::Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        serde_json::Value::Null => py.None().into_bound(py),
+        serde_json::Value::Bool(value) => PyBool::new(py, *value).to_owned().into_any(),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(integer) => integer.into_pyobject(py)?.into_any(),
+            None => number
+                .as_f64()
+                .unwrap_or(f64::NAN)
+                .into_pyobject(py)?
+                .into_any(),
+        },
+        serde_json::Value::String(string) => string.into_pyobject(py)?.into_any(),
+        serde_json::Value::Array(values) => {
+            let list = PyList::empty(py);
+            for value in values {
+                list.append(json_to_py(py, value)?)?;
+            }
+            list.into_any()
+        }
+        serde_json::Value::Object(values) => {
+            let dict = PyDict::new(py);
+            for (key, value) in values {
+                dict.set_item(key, json_to_py(py, value)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+fn py_to_toml(value: &Bound<'_, PyAny>) -> PyResult<toml::Value> {
+    if let Ok(value) = value.extract::<bool>() {
+        Ok(toml::Value::Boolean(value))
+    } else if let Ok(value) = value.extract::<i64>() {
+        Ok(toml::Value::Integer(value))
+    } else if let Ok(value) = value.extract::<f64>() {
+        Ok(toml::Value::Float(value))
+    } else if let Ok(value) = value.extract::<String>() {
+        Ok(toml::Value::String(value))
+    } else {
+        Err(PyTypeError::new_err(
+            "only bool, int, float and str values can be set",
+        ))
+    }
+}
+
+/// A config file with its bodies and test particles
+#[pyclass(name = "ConfigSystem")]
+#[derive(Clone)]
+pub struct PyConfigSystem {
+    inner: ConfigSystem,
+}
+
+#[pymethods]
+impl PyConfigSystem {
+    /// Reads a config file, the format is taken from the extension
+    #[staticmethod]
+    fn load(path: String) -> PyResult<Self> {
+        Ok(Self {
+            inner: ConfigSystem::parse(path)?,
+        })
+    }
+
+    /// Parses a config from a toml string
+    #[staticmethod]
+    fn from_toml(text: &str) -> PyResult<Self> {
+        Ok(Self {
+            inner: toml::from_str(text).map_err(ApplicationError::from)?,
+        })
+    }
+
+    fn to_toml(&self) -> PyResult<String> {
+        Ok(toml::to_string(&self.inner).map_err(ApplicationError::from)?)
+    }
+
+    /// The value at a path like `config.timestep` or `system.bodies[Earth].velocity.y`
+    fn get<'py>(&self, py: Python<'py>, path: &str) -> PyResult<Bound<'py, PyAny>> {
+        let value = get_value(&self.inner, path)?;
+        let value =
+            serde_json::to_value(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        json_to_py(py, &value)
+    }
+
+    /// Replaces the value at a path like `config.timestep` or `system.bodies[Earth].velocity.y`
+    fn set(&mut self, path: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.inner = set_value(&self.inner, path, py_to_toml(value)?)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn body_names(&self) -> Vec<String> {
+        self.inner
+            .system
+            .bodies
+            .iter()
+            .map(|body| body.name.clone())
+            .collect()
+    }
+
+    /// Runs the simulation and records the state every `record_every` steps,
+    /// by default every `export_step` steps. The exports enabled in the
+    /// config are written as well.
+    #[pyo3(signature = (record_every=None))]
+    fn run(&self, py: Python<'_>, record_every: Option<i64>) -> PyResult<Trajectory> {
+        let configsystem = self.inner.clone();
+        let record_every = record_every.unwrap_or(configsystem.config.export_step);
+        if record_every < 1 {
+            return Err(PyValueError::new_err("record_every has to be at least 1"));
+        }
+        py.detach(|| record(configsystem, record_every))
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ConfigSystem({} bodies, {} test particles, {} steps of {} s)",
+            self.inner.system.bodies.len(),
+            self.inner.test_particles.len(),
+            self.inner.config.steps,
+            self.inner.config.timestep
+        )
+    }
+}
+
+/// The states recorded during a run, flattened in row-major order
+#[pyclass]
+pub struct Trajectory {
+    names: Vec<String>,
+    test_particle_count: usize,
+    steps: Vec<i64>,
+    times: Vec<f64>,
+    positions: Vec<f64>,
+    velocities: Vec<f64>,
+    test_particle_positions: Vec<f64>,
+    test_particle_velocities: Vec<f64>,
+    /// Only missing while the run is recorded
+    summary: Option<RunSummary>,
+}
+
+fn record(configsystem: ConfigSystem, record_every: i64) -> Result<Trajectory, ApplicationError> {
+    let mut simulation = Simulation::builder(configsystem.config, configsystem.system)
+        .test_particles(configsystem.test_particles)
+        .body_properties(configsystem.body_properties)
+        .build()?;
+
+    let mut trajectory = Trajectory {
+        names: simulation
+            .system()
+            .bodies
+            .iter()
+            .map(|body| body.name.clone())
+            .collect(),
+        test_particle_count: simulation.test_particles().len(),
+        steps: vec![],
+        times: vec![],
+        positions: vec![],
+        velocities: vec![],
+        test_particle_positions: vec![],
+        test_particle_velocities: vec![],
+        summary: None,
+    };
+    trajectory.record(&simulation);
+    while !simulation.is_done() {
+        simulation.step()?;
+        if simulation.steps() % record_every == 0 {
+            trajectory.record(&simulation);
+        }
+    }
+    let (_, summary) = simulation.finish();
+    trajectory.summary = Some(summary);
+    Ok(trajectory)
+}
+
+impl Trajectory {
+    fn record(&mut self, simulation: &Simulation) {
+        self.steps.push(simulation.steps());
+        self.times.push(simulation.time());
+        for body in simulation.system().bodies.iter() {
+            self.positions.extend([body.position.x, body.position.y]);
+            self.velocities.extend([body.velocity.x, body.velocity.y]);
+        }
+        for particle in simulation.test_particles() {
+            self.test_particle_positions
+                .extend([particle.position.x, particle.position.y]);
+            self.test_particle_velocities
+                .extend([particle.velocity.x, particle.velocity.y]);
+        }
+    }
+
+    fn array3<'py>(
+        &self,
+        py: Python<'py>,
+        values: &[f64],
+        count: usize,
+    ) -> PyResult<Bound<'py, PyArray3<f64>>> {
+        PyArray1::from_slice(py, values).reshape(self.shape(count))
+    }
+
+    /// The shape of the arrays of `count` bodies or test particles
+    fn shape(&self, count: usize) -> [usize; 3] {
+        [self.times.len(), count, 2]
+    }
+
+    /// The positions of the body at `index`, flattened like `positions`
+    fn body_positions(&self, index: usize) -> Vec<f64> {
+        self.positions
+            .chunks(2 * self.names.len())
+            .flat_map(|record| [record[2 * index], record[2 * index + 1]])
+            .collect()
+    }
+}
+
+#[pymethods]
+impl Trajectory {
+    /// The names of the bodies, in the order of the second axis of `positions`
+    #[getter]
+    fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    /// The recorded steps, shape `(records,)`
+    #[getter]
+    fn steps<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<i64>> {
+        PyArray1::from_slice(py, &self.steps)
+    }
+
+    /// The simulated time of the records in seconds, shape `(records,)`
+    #[getter]
+    fn time<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        PyArray1::from_slice(py, &self.times)
+    }
+
+    /// Shape `(records, bodies, 2)`
+    #[getter]
+    fn positions<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<f64>>> {
+        self.array3(py, &self.positions, self.names.len())
+    }
+
+    /// Shape `(records, bodies, 2)`
+    #[getter]
+    fn velocities<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<f64>>> {
+        self.array3(py, &self.velocities, self.names.len())
+    }
+
+    /// Shape `(records, test particles, 2)`
+    #[getter]
+    fn test_particle_positions<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<f64>>> {
+        self.array3(py, &self.test_particle_positions, self.test_particle_count)
+    }
+
+    /// Shape `(records, test particles, 2)`
+    #[getter]
+    fn test_particle_velocities<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<Bound<'py, PyArray3<f64>>> {
+        self.array3(py, &self.test_particle_velocities, self.test_particle_count)
+    }
+
+    /// The positions of one body, shape `(records, 2)`
+    fn body<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let index = self
+            .names
+            .iter()
+            .position(|body| body == name)
+            .ok_or_else(|| ApplicationError::UnknownBodyError(name.to_owned()))?;
+        PyArray1::from_vec(py, self.body_positions(index)).reshape([self.times.len(), 2])
+    }
+
+    /// The run summary as a dict
+    #[getter]
+    fn summary<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let value = serde_json::to_value(&self.summary)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        json_to_py(py, &value)
+    }
+
+    fn __len__(&self) -> usize {
+        self.times.len()
+    }
+}
+
+/// Reads a config file, the format is taken from the extension
+#[pyfunction]
+fn load(path: String) -> PyResult<PyConfigSystem> {
+    PyConfigSystem::load(path)
+}
+
+/// Runs a config, given as `ConfigSystem` or as path of a config file
+#[pyfunction]
+#[pyo3(signature = (config, record_every=None))]
+fn run(
+    py: Python<'_>,
+    config: &Bound<'_, PyAny>,
+    record_every: Option<i64>,
+) -> PyResult<Trajectory> {
+    let configsystem = match config.extract::<String>() {
+        Ok(path) => PyConfigSystem::load(path)?,
+        Err(_) => config.extract::<PyConfigSystem>()?,
+    };
+    configsystem.run(py, record_every)
+}
+
+/// Python bindings, built with `maturin` and the `python` feature
+#[pymodule]
+#[pyo3(name = "kepler_cli")]
+fn kepler_cli_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyConfigSystem>()?;
+    m.add_class::<Trajectory>()?;
+    m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::record;
+    use crate::configsystem::ConfigSystem;
+
+    #[test]
+    pub fn records_every_nth_step_in_row_major_order() {
+        let directory =
+            std::env::temp_dir().join(format!("kepler_cli_python_{}", std::process::id()));
+        let contents = format!(
+            r#"
+            [config]
+            timestep = 3600.0
+            steps = 10
+            export_step = 5
+            export_directory = "{}"
+            export_file_name_prefix = "SIM"
+            plot_system = false
+
+            [[system.bodies]]
+            name = "Sun"
+            mass = 1.989e30
+            position = {{ x = 0.0, y = 0.0 }}
+            velocity = {{ x = 0.0, y = 0.0 }}
+
+            [[system.bodies]]
+            name = "Earth"
+            mass = 5.972e24
+            position = {{ x = 1.496e11, y = 0.0 }}
+            velocity = {{ x = 0.0, y = 2.978e4 }}
+
+            [[system.test_particles]]
+            position = {{ x = 0.0, y = 1.496e11 }}
+            velocity = {{ x = -2.978e4, y = 0.0 }}
+            "#,
+            directory.display()
+        );
+        let configsystem: ConfigSystem = toml::from_str(&contents).unwrap();
+
+        let trajectory = record(configsystem, 2).unwrap();
+        assert_eq!(trajectory.names, ["Sun", "Earth"]);
+        assert_eq!(trajectory.steps, [0, 2, 4, 6, 8, 10]);
+        assert_eq!(trajectory.times[1], 7200.0);
+        assert_eq!(trajectory.shape(2), [6, 2, 2]);
+        assert_eq!(trajectory.positions.len(), 6 * 2 * 2);
+        assert_eq!(trajectory.velocities.len(), 6 * 2 * 2);
+        assert_eq!(trajectory.test_particle_positions.len(), 6 * 2);
+        assert_eq!(trajectory.test_particle_velocities.len(), 6 * 2);
+
+        let earth = trajectory.body_positions(1);
+        assert_eq!(earth.len(), 6 * 2);
+        assert_eq!(earth[..2], [1.496e11, 0.0]);
+        assert_eq!(earth[10..], trajectory.positions[22..]);
+        assert!(trajectory.summary.is_some());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
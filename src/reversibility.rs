@@ -0,0 +1,248 @@
+use kepler_core::types::System;
+use maths_rs::Vec2d;
+use serde::Serialize;
+
+use crate::{
+    configsystem::{Config, TestParticle},
+    error::ApplicationError,
+    frame::{apply_frame, Frame},
+    simulation::Simulation,
+};
+
+/// How far a body ended up from its initial state
+#[derive(Debug, Clone, Serialize)]
+pub struct BodyError {
+    pub name: String,
+    pub position_error: f64,
+    pub velocity_error: f64,
+}
+
+/// The result of integrating forward and back again
+#[derive(Debug, Clone, Serialize)]
+pub struct ReversibilityReport {
+    /// Steps in each direction
+    pub steps: i64,
+    pub timestep: f64,
+    pub bodies: Vec<BodyError>,
+    pub max_position_error: f64,
+    pub max_velocity_error: f64,
+    /// Largest position error relative to the largest initial distance from the origin
+    pub relative_position_error: f64,
+    /// Largest velocity error relative to the largest initial speed
+    pub relative_velocity_error: f64,
+    /// Largest position error of the test particles, if there are any
+    pub max_test_particle_position_error: Option<f64>,
+    /// Largest velocity error of the test particles, if there are any
+    pub max_test_particle_velocity_error: Option<f64>,
+}
+
+fn length(vector: Vec2d) -> f64 {
+    (vector.x * vector.x + vector.y * vector.y).sqrt()
+}
+
+fn relative(error: f64, scale: f64) -> f64 {
+    if scale > 0.0 {
+        error / scale
+    } else {
+        error
+    }
+}
+
+/// Runs all steps of the config without exports and returns the final state
+fn integrate(
+    config: Config,
+    system: System,
+    test_particles: Vec<TestParticle>,
+) -> Result<(System, Vec<TestParticle>), ApplicationError> {
+    let mut simulation = Simulation::builder(config, system)
+        .test_particles(test_particles)
+        .exports(false)
+        .build()?;
+    while !simulation.is_done() {
+        simulation.step()?;
+    }
+    let test_particles = simulation.test_particles().to_vec();
+    let (system, _) = simulation.finish();
+    Ok((system, test_particles))
+}
+
+/// Integrates `steps` steps forward and the same number of steps backward and
+/// compares the result with the initial state, in the frame of the config.
+///
+/// An exactly time-symmetric integrator only deviates by round-off.
+pub fn check_reversibility(
+    config: &Config,
+    mut system: System,
+    mut test_particles: Vec<TestParticle>,
+    steps: i64,
+) -> Result<ReversibilityReport, ApplicationError> {
+    apply_frame(&config.frame, &mut system, &mut test_particles)?;
+    let initial_system = system.clone();
+    let initial_test_particles = test_particles.clone();
+
+    let forward = Config {
+        steps,
+        frame: Frame::AsGiven,
+        ..config.clone()
+    };
+    let backward = Config {
+        reverse: !forward.reverse,
+        ..forward.clone()
+    };
+    let timestep = forward.signed_timestep();
+    let (system, test_particles) = integrate(forward, system, test_particles)?;
+    let (system, test_particles) = integrate(backward, system, test_particles)?;
+
+    let bodies: Vec<BodyError> = initial_system
+        .bodies
+        .iter()
+        .zip(system.bodies.iter())
+        .map(|(initial, last)| BodyError {
+            name: initial.name.clone(),
+            position_error: length(last.position - initial.position),
+            velocity_error: length(last.velocity - initial.velocity),
+        })
+        .collect();
+    let max_position_error = bodies
+        .iter()
+        .map(|body| body.position_error)
+        .fold(0.0, f64::max);
+    let max_velocity_error = bodies
+        .iter()
+        .map(|body| body.velocity_error)
+        .fold(0.0, f64::max);
+    let extent = initial_system
+        .bodies
+        .iter()
+        .map(|body| length(body.position))
+        .fold(0.0, f64::max);
+    let max_speed = initial_system
+        .bodies
+        .iter()
+        .map(|body| length(body.velocity))
+        .fold(0.0, f64::max);
+
+    let test_particle_errors = |error: fn(&TestParticle, &TestParticle) -> f64| {
+        initial_test_particles
+            .iter()
+            .zip(test_particles.iter())
+            .map(|(initial, last)| error(initial, last))
+            .reduce(f64::max)
+    };
+
+    Ok(ReversibilityReport {
+        steps,
+        timestep,
+        bodies,
+        max_position_error,
+        max_velocity_error,
+        relative_position_error: relative(max_position_error, extent),
+        relative_velocity_error: relative(max_velocity_error, max_speed),
+        max_test_particle_position_error: test_particle_errors(|initial, last| {
+            length(last.position - initial.position)
+        }),
+        max_test_particle_velocity_error: test_particle_errors(|initial, last| {
+            length(last.velocity - initial.velocity)
+        }),
+    })
+}
+
+impl std::fmt::Display for ReversibilityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} steps of {} s forward and back",
+            self.steps, self.timestep
+        )?;
+        let width = self
+            .bodies
+            .iter()
+            .map(|body| body.name.len())
+            .chain(["Test particles".len()])
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            f,
+            "{:<width$}  {:>22}  {:>22}",
+            "", "Position error", "Velocity error"
+        )?;
+        for body in self.bodies.iter() {
+            writeln!(
+                f,
+                "{:<width$}  {:>20e} m  {:>18e} m/s",
+                body.name, body.position_error, body.velocity_error
+            )?;
+        }
+        if let (Some(position_error), Some(velocity_error)) = (
+            self.max_test_particle_position_error,
+            self.max_test_particle_velocity_error,
+        ) {
+            writeln!(
+                f,
+                "{:<width$}  {:>20e} m  {:>18e} m/s",
+                "Test particles", position_error, velocity_error
+            )?;
+        }
+        writeln!(
+            f,
+            "Max. relative position error  {:e}",
+            self.relative_position_error
+        )?;
+        writeln!(
+            f,
+            "Max. relative velocity error  {:e}",
+            self.relative_velocity_error
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_reversibility;
+    use crate::{
+        configsystem::{Config, TestParticle},
+        generate::{circular_speed, ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS},
+    };
+    use kepler_core::types::{Body, System};
+    use maths_rs::Vec2d;
+
+    #[test]
+    pub fn returns_close_to_the_initial_state() {
+        let contents = r#"
+            timestep = 3600.0
+            steps = 1000
+            export_step = 1000
+            export_directory = "export_files"
+            export_file_name_prefix = "SIM"
+        "#;
+        let config: Config = toml::from_str(contents).unwrap();
+        let speed = circular_speed(SUN_MASS, ASTRONOMICAL_UNIT);
+        let system = System {
+            bodies: vec![
+                Body {
+                    name: "Sun".to_owned(),
+                    mass: SUN_MASS,
+                    position: Vec2d::new(0.0, 0.0),
+                    velocity: Vec2d::new(0.0, 0.0),
+                },
+                Body {
+                    name: "Earth".to_owned(),
+                    mass: EARTH_MASS,
+                    position: Vec2d::new(ASTRONOMICAL_UNIT, 0.0),
+                    velocity: Vec2d::new(0.0, speed),
+                },
+            ],
+        };
+        let test_particles = vec![TestParticle {
+            position: Vec2d::new(-ASTRONOMICAL_UNIT, 0.0),
+            velocity: Vec2d::new(0.0, -speed),
+        }];
+
+        let report = check_reversibility(&config, system, test_particles, 1000).unwrap();
+        assert_eq!(report.bodies.len(), 2);
+        assert_eq!(report.bodies[1].name, "Earth");
+        assert!(report.relative_position_error < 1e-3, "{report}");
+        assert!(report.relative_velocity_error < 1e-3, "{report}");
+        assert!(report.max_test_particle_position_error.unwrap() < 1e-3 * ASTRONOMICAL_UNIT);
+    }
+}
@@ -1,166 +1,507 @@
 use kepler_core::{
     energy::{calculate_kinetic_energy, calculate_potential_energy, calculate_system_energy},
-    mover::system_timestep,
     types::System,
 };
 use maths_rs::num::Cast;
+use std::collections::BTreeMap;
 
 use crate::{
-    configsystem::Config,
+    chaos::{ChaosIndicators, ChaosTracker},
+    configsystem::{BodyProperties, Config, StdoutFormat, TestParticle},
+    error::ApplicationError,
     export::{
-        export_system_parameters_to_csv, export_system_snapshot_to_csv,
-        export_system_to_csv_by_body,
+        export_chaos_indicators_to_csv, export_min_distances_to_csv, export_state_to_jsonl,
+        export_summary_to_json, export_system_parameters_to_csv, export_system_snapshot_to_csv,
+        export_system_to_csv_by_body, export_test_particles_to_csv,
     },
+    frame::{apply_frame, relative_to},
+    mover::Mover,
     plot::{plot_total_energy, PlotDatum},
+    stability::{check_stability, Severity},
+    summary::{RunSummary, SummaryTracker},
 };
 
-pub fn run_simulation(config: Config, initial_system: System) {
-    let mut energy_plot_data: Vec<PlotDatum> = vec![];
+/// Observes a running simulation, e.g. to show it live or to collect data
+///
+/// All methods have empty default implementations, so only the hooks that are
+/// needed have to be implemented.
+pub trait Monitor {
+    /// Called once with the initial state, after it was transformed into the simulation frame
+    fn start(&mut self, _config: &Config, _system: &System, _test_particles: &[TestParticle]) {}
 
-    let mut system = initial_system.clone();
+    /// Called after every step, returning `false` stops the simulation early
+    fn step(
+        &mut self,
+        _config: &Config,
+        _system: &System,
+        _test_particles: &[TestParticle],
+        _step: i64,
+        _time: f64,
+    ) -> bool {
+        true
+    }
+}
 
-    let mut time = 0.0;
-    if config.export_system_parameters_history {
-        match export_system_parameters_to_csv(&config, &system, 0, time) {
-            Ok(_) => {
-                tracing::event!(tracing::Level::DEBUG, "Exported 0, time {time}s");
-            }
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "error while exporting {e}");
-                return;
-            }
-        };
+/// Sets up a [`Simulation`]
+///
+/// ```no_run
+/// use kepler_cli::{configsystem::ConfigSystem, simulation::Simulation};
+///
+/// let configsystem = ConfigSystem::parse("example.toml".to_owned())?;
+/// let (system, summary) = Simulation::builder(configsystem.config, configsystem.system)
+///     .test_particles(configsystem.test_particles)
+///     .body_properties(configsystem.body_properties)
+///     .build()?
+///     .run()?;
+/// println!("{summary}");
+/// # Ok::<(), kepler_cli::error::ApplicationError>(())
+/// ```
+pub struct SimulationBuilder {
+    config: Config,
+    system: System,
+    test_particles: Vec<TestParticle>,
+    body_properties: BTreeMap<String, BodyProperties>,
+    monitors: Vec<Box<dyn Monitor>>,
+    exports: bool,
+}
+
+impl SimulationBuilder {
+    pub fn new(config: Config, system: System) -> Self {
+        Self {
+            config,
+            system,
+            test_particles: vec![],
+            body_properties: BTreeMap::new(),
+            monitors: vec![],
+            exports: true,
+        }
     }
-    if config.export_system_state {
-        match export_system_snapshot_to_csv(&config, &system, 0, time) {
-            Ok(_) => {
-                tracing::event!(tracing::Level::DEBUG, "Exported 0, time {time}s");
-            }
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "error while exporting {e}");
 
-                println!("error while exporting {e}");
-                return;
-            }
-        };
+    /// Massless particles that are moved by the bodies but do not attract anything
+    pub fn test_particles(mut self, test_particles: Vec<TestParticle>) -> Self {
+        self.test_particles = test_particles;
+        self
     }
 
-    if config.export_body_history {
-        match export_system_to_csv_by_body(&config, &system, 0, time) {
-            Ok(_) => {
-                tracing::event!(tracing::Level::DEBUG, "Exported 0, time {time}s");
-            }
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "error while exporting {e}");
+    /// Radius and density of the bodies by name, for the Roche limit check
+    pub fn body_properties(mut self, body_properties: BTreeMap<String, BodyProperties>) -> Self {
+        self.body_properties = body_properties;
+        self
+    }
+
+    /// Adds a monitor that is called after every step
+    pub fn monitor(mut self, monitor: impl Monitor + 'static) -> Self {
+        self.monitors.push(Box::new(monitor));
+        self
+    }
+
+    /// Adds an already boxed monitor, e.g. one chosen at runtime
+    pub fn boxed_monitor(mut self, monitor: Box<dyn Monitor>) -> Self {
+        self.monitors.push(monitor);
+        self
+    }
+
+    /// Whether the exports enabled in the config, the energy plot and the summary
+    /// are written, on by default. Without them no chaos indicators are computed,
+    /// so a simulation that is only stepped to look at its state costs no more
+    /// than the integration.
+    pub fn exports(mut self, exports: bool) -> Self {
+        self.exports = exports;
+        self
+    }
 
-                println!("error while exporting {e}");
-                return;
+    /// Checks the stability of the initial state, transforms it into the frame
+    /// of the config and writes the exports of step 0
+    pub fn build(self) -> Result<Simulation, ApplicationError> {
+        let Self {
+            config,
+            mut system,
+            mut test_particles,
+            body_properties,
+            mut monitors,
+            exports,
+        } = self;
+        let stability = check_stability(&system, &body_properties);
+        stability.log();
+        if config.strict_stability && stability.is_unstable() {
+            let findings: Vec<&str> = stability
+                .findings
+                .iter()
+                .filter(|finding| finding.severity == Severity::Unstable)
+                .map(|finding| finding.message.as_str())
+                .collect();
+            return Err(ApplicationError::UnstableSystemError(findings.join(", ")));
+        }
+        apply_frame(&config.frame, &mut system, &mut test_particles)?;
+        let mover = Mover::new(config.threads);
+        let tracker = SummaryTracker::new(&system, config.close_encounter_distance);
+        let chaos = if exports && config.export_chaos_indicators {
+            let chaos = ChaosTracker::new(&system, config.shadow_perturbation);
+            if chaos.is_none() {
+                tracing::event!(
+                    tracing::Level::WARN,
+                    "The system cannot be perturbed, no chaos indicators are computed"
+                );
             }
+            chaos
+        } else {
+            None
         };
+        for monitor in monitors.iter_mut() {
+            monitor.start(&config, &system, &test_particles);
+        }
+        if exports {
+            export_step(
+                &config,
+                &system,
+                &test_particles,
+                chaos.as_ref().map(ChaosTracker::indicators),
+                0,
+                0.0,
+            )?;
+        }
+
+        Ok(Simulation {
+            config,
+            system,
+            test_particles,
+            monitors,
+            mover,
+            tracker,
+            chaos,
+            energy_plot_data: vec![],
+            exports,
+            step: 0,
+            time: 0.0,
+        })
     }
+}
 
-    for i in 1..config.steps + 1 {
-        system = system_timestep(system, config.timestep);
-        time += config.timestep;
+/// A simulation that can be advanced step by step or run to the end
+///
+/// The exports enabled in the config are written while stepping, the energy
+/// plot and the summary when the simulation is finished.
+pub struct Simulation {
+    config: Config,
+    system: System,
+    test_particles: Vec<TestParticle>,
+    monitors: Vec<Box<dyn Monitor>>,
+    mover: Mover,
+    tracker: SummaryTracker,
+    chaos: Option<ChaosTracker>,
+    energy_plot_data: Vec<PlotDatum>,
+    exports: bool,
+    step: i64,
+    time: f64,
+}
+
+impl Simulation {
+    pub fn builder(config: Config, system: System) -> SimulationBuilder {
+        SimulationBuilder::new(config, system)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The current state of the bodies, in the frame of the simulation
+    pub fn system(&self) -> &System {
+        &self.system
+    }
+
+    pub fn test_particles(&self) -> &[TestParticle] {
+        &self.test_particles
+    }
 
-        if i % config.export_step == 0 {
+    /// The number of steps done so far
+    pub fn steps(&self) -> i64 {
+        self.step
+    }
+
+    /// The simulated time in seconds, negative when integrating backwards
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Whether all steps of the config are done
+    pub fn is_done(&self) -> bool {
+        self.step >= self.config.steps
+    }
+
+    /// Advances the simulation by one timestep.
+    ///
+    /// Returns `false` if one of the monitors asked to stop.
+    pub fn step(&mut self) -> Result<bool, ApplicationError> {
+        let config = &self.config;
+        let timestep = config.signed_timestep();
+        // the test particles have to see the bodies before they are moved
+        self.mover
+            .timestep_test_particles(&self.system, &mut self.test_particles, timestep);
+        let system = std::mem::replace(&mut self.system, System { bodies: vec![] });
+        self.system = self.mover.timestep(system, timestep);
+        self.time += timestep;
+        self.step += 1;
+        self.tracker.track_step(&self.system, self.time);
+        if let Some(chaos) = &mut self.chaos {
+            chaos.track_step(&self.mover, &self.system, timestep);
+        }
+
+        if self.step % config.export_step == 0 {
+            self.tracker.track_export(&self.system);
+        }
+        if self.exports && self.step % config.export_step == 0 {
             if config.plot_system {
-                // save data for plotting
-                let kinetic_energy = match config.plot_system_kinetic_energy {
-                    true => Some(system.bodies.iter().map(calculate_kinetic_energy).sum()),
-                    false => None,
-                };
-                let potential_energy = match config.plot_system_potential_energy {
-                    true => Some(
-                        system
-                            .bodies
-                            .iter()
-                            .map(|body| {
-                                system
-                                    .bodies
-                                    .iter()
-                                    .map(|other| calculate_potential_energy(body, other))
-                                    .sum::<f64>()
-                            })
-                            .sum::<f64>(),
-                    ),
-                    false => None,
-                };
-                energy_plot_data.push(PlotDatum {
-                    time,
-                    total_energy: calculate_system_energy(&system),
-                    kinetic_energy,
-                    potential_energy,
-                });
+                self.energy_plot_data
+                    .push(plot_datum(config, &self.system, self.time));
             }
 
             // writing to file
-            if config.export_system_parameters_history {
-                match export_system_parameters_to_csv(&config, &system, i, time) {
-                    Ok(_) => {
-                        tracing::event!(tracing::Level::DEBUG, "Exported 0, time {time}s");
-                    }
-                    Err(e) => {
-                        tracing::event!(tracing::Level::ERROR, "error while exporting {e}");
-                        return;
-                    }
-                };
-            }
-            if config.export_system_state {
-                match export_system_snapshot_to_csv(&config, &system, i, time) {
-                    Ok(_) => {
-                        tracing::event!(tracing::Level::DEBUG, "Exported 0, time {time}s");
-                    }
-                    Err(e) => {
-                        tracing::event!(tracing::Level::ERROR, "error while exporting {e}");
-                        return;
-                    }
-                };
-            }
+            export_step(
+                config,
+                &self.system,
+                &self.test_particles,
+                self.chaos.as_ref().map(ChaosTracker::indicators),
+                self.step,
+                self.time,
+            )?;
+        }
 
-            if config.export_body_history {
-                match export_system_to_csv_by_body(&config, &system, i, time) {
-                    Ok(_) => {
-                        tracing::event!(tracing::Level::DEBUG, "Exported 0, time {time}s");
-                    }
-                    Err(e) => {
-                        tracing::event!(tracing::Level::ERROR, "error while exporting {e}");
-                        return;
-                    }
-                };
+        let mut running = true;
+        for monitor in self.monitors.iter_mut() {
+            running &= monitor.step(
+                config,
+                &self.system,
+                &self.test_particles,
+                self.step,
+                self.time,
+            );
+        }
+        Ok(running)
+    }
+
+    /// Runs the remaining steps and finishes the simulation
+    pub fn run(mut self) -> Result<(System, RunSummary), ApplicationError> {
+        while !self.is_done() {
+            if !self.step()? {
+                tracing::event!(tracing::Level::INFO, "Stopped after {} steps", self.step);
+                break;
             }
+        }
+        Ok(self.finish())
+    }
+
+    /// Plots the energy, writes the summary and returns the final state
+    /// of the system together with the summary
+    pub fn finish(self) -> (System, RunSummary) {
+        // the monitors may have to restore the terminal before anything is printed
+        drop(self.monitors);
 
-            let human_readable_time = format_time(time.as_u64());
-            let progress = i.as_f64() / config.steps.as_f64() * 100.0;
+        if self.exports && self.config.plot_system {
+            match plot_total_energy(self.energy_plot_data, &self.config) {
+                Ok(_) => {
+                    tracing::event!(tracing::Level::INFO, "Plotted total energy");
+                }
+                Err(e) => {
+                    tracing::event!(
+                        tracing::Level::ERROR,
+                        "Error while plotting total energy: {e}"
+                    );
+                }
+            };
+        }
+
+        if self.exports && self.config.export_min_distances {
+            match export_min_distances_to_csv(&self.config, self.tracker.min_distances()) {
+                Ok(_) => {
+                    tracing::event!(tracing::Level::DEBUG, "Exported min distances");
+                }
+                Err(e) => {
+                    tracing::event!(
+                        tracing::Level::ERROR,
+                        "Error while exporting min distances: {e}"
+                    );
+                }
+            };
+        }
+
+        let summary = self.tracker.finish(
+            &self.system,
+            self.step,
+            self.time,
+            self.chaos.as_ref().map(ChaosTracker::indicators),
+        );
+        if self.exports {
+            match export_summary_to_json(&self.config, &summary) {
+                Ok(_) => {
+                    tracing::event!(tracing::Level::DEBUG, "Exported summary");
+                }
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "Error while exporting summary: {e}");
+                }
+            };
+        }
 
+        (self.system, summary)
+    }
+}
+
+/// Runs the simulation and returns the final state of the system
+/// together with a summary of the run.
+pub fn run_simulation(
+    config: Config,
+    initial_system: System,
+    test_particles: Vec<TestParticle>,
+) -> Result<(System, RunSummary), ApplicationError> {
+    Simulation::builder(config, initial_system)
+        .test_particles(test_particles)
+        .build()?
+        .run()
+}
+
+/// The energies plotted for the current step
+fn plot_datum(config: &Config, system: &System, time: f64) -> PlotDatum {
+    let kinetic_energy = match config.plot_system_kinetic_energy {
+        true => Some(system.bodies.iter().map(calculate_kinetic_energy).sum()),
+        false => None,
+    };
+    let potential_energy = match config.plot_system_potential_energy {
+        true => Some(
+            system
+                .bodies
+                .iter()
+                .map(|body| {
+                    system
+                        .bodies
+                        .iter()
+                        .map(|other| calculate_potential_energy(body, other))
+                        .sum::<f64>()
+                })
+                .sum::<f64>(),
+        ),
+        false => None,
+    };
+    PlotDatum {
+        time,
+        total_energy: calculate_system_energy(system),
+        kinetic_energy,
+        potential_energy,
+    }
+}
+
+/// Logs the outcome of one exporter and passes its error on
+fn log_export(
+    exporter: &str,
+    step: i64,
+    time: f64,
+    result: Result<(), ApplicationError>,
+) -> Result<(), ApplicationError> {
+    match &result {
+        Ok(()) => {
+            tracing::event!(
+                tracing::Level::DEBUG,
+                "Exported {exporter} of step {step}, time {time}s"
+            );
+        }
+        Err(e) => {
             tracing::event!(
-                tracing::Level::INFO,
-                "Progress: {:.2}%, time: {}",
-                progress,
-                human_readable_time,
+                tracing::Level::ERROR,
+                "Error while exporting {exporter}: {e}"
             );
         }
     }
+    result
+}
+
+/// Writes all exports enabled in the config for the current step.
+fn export_step(
+    config: &Config,
+    system: &System,
+    test_particles: &[TestParticle],
+    chaos_indicators: Option<ChaosIndicators>,
+    step: i64,
+    time: f64,
+) -> Result<(), ApplicationError> {
+    if config.export_system_parameters_history {
+        log_export(
+            "the system parameters",
+            step,
+            time,
+            export_system_parameters_to_csv(config, system, step, time),
+        )?;
+    }
 
-    if config.plot_system {
-        match plot_total_energy(energy_plot_data, &config) {
-            Ok(_) => {
-                tracing::event!(tracing::Level::INFO, "Plotted total energy");
-            }
-            Err(e) => {
-                tracing::event!(
-                    tracing::Level::ERROR,
-                    "Error while plotting total energy: {e}"
-                );
-            }
-        };
+    // the states of the bodies can be exported relative to one of them,
+    // the system parameters are always given in the frame of the simulation
+    let relative = match &config.export_relative_to {
+        Some(name) => Some(relative_to(name, system, test_particles)?),
+        None => None,
+    };
+    let (exported_system, test_particles) = match &relative {
+        Some((system, test_particles)) => (system, test_particles.as_slice()),
+        None => (system, test_particles),
+    };
+
+    if config.export_system_state {
+        log_export(
+            "the system state",
+            step,
+            time,
+            export_system_snapshot_to_csv(config, exported_system, step, time),
+        )?;
+    }
+
+    if config.export_body_history {
+        log_export(
+            "the body history",
+            step,
+            time,
+            export_system_to_csv_by_body(config, exported_system, step, time),
+        )?;
     }
+
+    if config.export_test_particle_history {
+        log_export(
+            "the test particles",
+            step,
+            time,
+            export_test_particles_to_csv(config, test_particles, step, time),
+        )?;
+    }
+
+    if let Some(indicators) = &chaos_indicators {
+        log_export(
+            "the chaos indicators",
+            step,
+            time,
+            export_chaos_indicators_to_csv(config, indicators, step, time),
+        )?;
+    }
+
+    if config.stdout == Some(StdoutFormat::Jsonl) {
+        let mut stdout = std::io::stdout().lock();
+        log_export(
+            "the state to stdout",
+            step,
+            time,
+            export_state_to_jsonl(
+                config,
+                system,
+                exported_system,
+                test_particles,
+                step,
+                time,
+                &mut stdout,
+            ),
+        )?;
+    }
+
+    Ok(())
 }
 
 /// This function formats time in seconds in a human readable format.
 /// It assumes one month is 30 days and one year is 12 * 30 days,
 /// so it's not extremely precise.
-fn format_time(time: u64) -> String {
+pub fn format_time(time: u64) -> String {
     let one_min = 60;
     let one_hour = one_min * 60; // 3600 seconds
     let one_day = one_hour * 24; // 86_400 seconds
@@ -187,9 +528,16 @@ fn format_time(time: u64) -> String {
     }
 }
 
+/// Formats a simulated time like `format_time`, with a minus sign for the
+/// negative times of a run integrated backwards.
+pub fn format_signed_time(time: f64) -> String {
+    let sign = if time < 0.0 { "-" } else { "" };
+    format!("{sign}{}", format_time(time.abs().as_u64()))
+}
+
 #[cfg(test)]
 mod test {
-    use super::format_time;
+    use super::{format_signed_time, format_time};
 
     #[test]
     pub fn correctly_formats_time() {
@@ -205,4 +553,12 @@ mod test {
         );
         assert_eq!("39.69y".to_string(), format_time(1_234_567_890));
     }
+
+    #[test]
+    pub fn keeps_the_sign_of_reversed_times() {
+        assert_eq!("6.00min".to_string(), format_signed_time(360.0));
+        assert_eq!("-6.00min".to_string(), format_signed_time(-360.0));
+        assert_eq!("-1.00days".to_string(), format_signed_time(-86_400.0));
+        assert_eq!("0s".to_string(), format_signed_time(-0.0));
+    }
 }
@@ -0,0 +1,429 @@
+use std::collections::BTreeMap;
+
+use kepler_core::types::{Body, System};
+use maths_rs::Vec2d;
+use serde::Serialize;
+
+use crate::{configsystem::BodyProperties, gravity::GRAVITATIONAL_CONSTANT};
+
+/// The dominant body has to be at least this many times as massive as any other body
+const DOMINANT_MASS_RATIO: f64 = 10.0;
+
+/// Two planets closer than 2√3 mutual Hill radii are Hill unstable (Gladman 1993)
+const HILL_STABLE_SEPARATION: f64 = 3.4641016151377544;
+
+/// Systems of three or more planets closer than about 10 mutual Hill radii
+/// usually become unstable within the lifetime of a star (Chambers et al. 1996)
+const LONG_TERM_SEPARATION: f64 = 10.0;
+
+/// How serious a finding of the stability checks is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Likely to become unstable over a long run
+    Marginal,
+    /// Obviously unstable, e.g. crossing orbits or a body inside a Roche limit
+    Unstable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// The separation of two neighbouring planets around the dominant body
+#[derive(Debug, Clone, Serialize)]
+pub struct HillSeparation {
+    pub inner: String,
+    pub outer: String,
+    /// Difference of the semi-major axes in m
+    pub distance: f64,
+    /// Difference of the semi-major axes in mutual Hill radii
+    pub mutual_hill_radii: f64,
+}
+
+/// How close a body with a density gets to the body it orbits
+#[derive(Debug, Clone, Serialize)]
+pub struct RocheLimit {
+    pub body: String,
+    pub primary: String,
+    /// Periapsis of the orbit about the primary, or the current distance if it is not bound
+    pub closest_distance: f64,
+    /// Roche limit of a rigid body in m
+    pub rigid: f64,
+    /// Roche limit of a fluid body in m
+    pub fluid: f64,
+}
+
+/// The result of the stability checks of a system
+#[derive(Debug, Clone, Serialize)]
+pub struct StabilityReport {
+    /// The body the planets orbit, if one body dominates the mass of the system
+    pub dominant_body: Option<String>,
+    pub hill_separations: Vec<HillSeparation>,
+    pub roche_limits: Vec<RocheLimit>,
+    pub findings: Vec<Finding>,
+}
+
+/// The osculating orbit of a body about a primary
+struct Orbit {
+    distance: f64,
+    /// `None` if the body is not bound to the primary
+    semi_major_axis: Option<f64>,
+    eccentricity: f64,
+}
+
+fn length(vector: Vec2d) -> f64 {
+    (vector.x * vector.x + vector.y * vector.y).sqrt()
+}
+
+fn orbit(body: &Body, primary: &Body) -> Orbit {
+    let position = body.position - primary.position;
+    let velocity = body.velocity - primary.velocity;
+    let mu = GRAVITATIONAL_CONSTANT * (body.mass + primary.mass);
+    let distance = length(position);
+    let speed = length(velocity);
+    let energy = speed * speed / 2.0 - mu / distance;
+    let angular_momentum = position.x * velocity.y - position.y * velocity.x;
+    Orbit {
+        distance,
+        semi_major_axis: (energy < 0.0).then(|| -mu / (2.0 * energy)),
+        eccentricity: (1.0 + 2.0 * energy * angular_momentum * angular_momentum / (mu * mu))
+            .max(0.0)
+            .sqrt(),
+    }
+}
+
+/// The index of the body that dominates the mass of the system
+fn dominant_body(system: &System) -> Option<usize> {
+    let (index, body) = system
+        .bodies
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.mass.total_cmp(&b.mass))?;
+    system
+        .bodies
+        .iter()
+        .enumerate()
+        .all(|(other, o)| other == index || body.mass >= DOMINANT_MASS_RATIO * o.mass)
+        .then_some(index)
+}
+
+/// The body every body orbits: the least massive heavier body in whose Hill sphere
+/// about the dominant body it is, or else the dominant body
+pub(crate) fn primaries(system: &System, dominant: usize) -> Vec<Option<usize>> {
+    let bodies = &system.bodies;
+    let hill_radius = |index: usize| {
+        length(bodies[index].position - bodies[dominant].position)
+            * (bodies[index].mass / (3.0 * bodies[dominant].mass)).cbrt()
+    };
+    (0..bodies.len())
+        .map(|index| {
+            if index == dominant {
+                return None;
+            }
+            let satellite_of = (0..bodies.len())
+                .filter(|&other| {
+                    other != index
+                        && other != dominant
+                        && bodies[other].mass > bodies[index].mass
+                        && length(bodies[index].position - bodies[other].position)
+                            < hill_radius(other)
+                })
+                .min_by(|&a, &b| bodies[a].mass.total_cmp(&bodies[b].mass));
+            Some(satellite_of.unwrap_or(dominant))
+        })
+        .collect()
+}
+
+/// Checks the initial state of a system for obvious instabilities.
+///
+/// Neighbouring planets of a dominant body are compared by their separation in
+/// mutual Hill radii, and bodies with a radius or density by how close they get to
+/// the Roche limit of the body they orbit. Without a dominant body only the Roche
+/// limits are checked, with the most massive other body as primary.
+pub fn check_stability(
+    system: &System,
+    body_properties: &BTreeMap<String, BodyProperties>,
+) -> StabilityReport {
+    let bodies = &system.bodies;
+    let dominant = dominant_body(system);
+    let mut findings = vec![];
+
+    let primaries: Vec<Option<usize>> = match dominant {
+        Some(dominant) => primaries(system, dominant),
+        None => (0..bodies.len())
+            .map(|index| {
+                (0..bodies.len())
+                    .filter(|&other| other != index)
+                    .max_by(|&a, &b| bodies[a].mass.total_cmp(&bodies[b].mass))
+            })
+            .collect(),
+    };
+
+    let mut hill_separations = vec![];
+    if let Some(dominant) = dominant {
+        let mut planets: Vec<(usize, f64, f64)> = vec![];
+        for (index, primary) in primaries.iter().enumerate() {
+            if *primary != Some(dominant) {
+                continue;
+            }
+            let orbit = orbit(&bodies[index], &bodies[dominant]);
+            match orbit.semi_major_axis {
+                Some(semi_major_axis) => planets.push((index, semi_major_axis, orbit.eccentricity)),
+                None => findings.push(Finding {
+                    severity: Severity::Marginal,
+                    message: format!(
+                        "{} is not bound to {}",
+                        bodies[index].name, bodies[dominant].name
+                    ),
+                }),
+            }
+        }
+        planets.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        for pair in planets.windows(2) {
+            let (inner, inner_axis, inner_eccentricity) = pair[0];
+            let (outer, outer_axis, outer_eccentricity) = pair[1];
+            let mutual_hill_radius =
+                ((bodies[inner].mass + bodies[outer].mass) / (3.0 * bodies[dominant].mass)).cbrt()
+                    * (inner_axis + outer_axis)
+                    / 2.0;
+            let separation = HillSeparation {
+                inner: bodies[inner].name.clone(),
+                outer: bodies[outer].name.clone(),
+                distance: outer_axis - inner_axis,
+                mutual_hill_radii: (outer_axis - inner_axis) / mutual_hill_radius,
+            };
+            if inner_axis * (1.0 + inner_eccentricity) >= outer_axis * (1.0 - outer_eccentricity) {
+                findings.push(Finding {
+                    severity: Severity::Unstable,
+                    message: format!(
+                        "The orbits of {} and {} cross",
+                        separation.inner, separation.outer
+                    ),
+                });
+            } else if separation.mutual_hill_radii < HILL_STABLE_SEPARATION {
+                findings.push(Finding {
+                    severity: Severity::Unstable,
+                    message: format!(
+                        "{} and {} are {:.2} mutual Hill radii apart, less than 2√3",
+                        separation.inner, separation.outer, separation.mutual_hill_radii
+                    ),
+                });
+            } else if planets.len() > 2 && separation.mutual_hill_radii < LONG_TERM_SEPARATION {
+                findings.push(Finding {
+                    severity: Severity::Marginal,
+                    message: format!(
+                        "{} and {} are {:.2} mutual Hill radii apart, tightly packed systems of three or more planets often become unstable in long runs",
+                        separation.inner, separation.outer, separation.mutual_hill_radii
+                    ),
+                });
+            }
+            hill_separations.push(separation);
+        }
+    }
+
+    let mut roche_limits = vec![];
+    for (index, body) in bodies.iter().enumerate() {
+        let (Some(primary), Some(density)) = (
+            primaries[index],
+            body_properties
+                .get(&body.name)
+                .and_then(|properties| properties.density(body.mass)),
+        ) else {
+            continue;
+        };
+        let primary = &bodies[primary];
+        let orbit = orbit(body, primary);
+        let scale = (3.0 * primary.mass / (4.0 * std::f64::consts::PI * density)).cbrt();
+        let limit = RocheLimit {
+            body: body.name.clone(),
+            primary: primary.name.clone(),
+            closest_distance: orbit
+                .semi_major_axis
+                .map_or(orbit.distance, |axis| axis * (1.0 - orbit.eccentricity)),
+            rigid: 2.0_f64.cbrt() * scale,
+            fluid: 2.44 * scale,
+        };
+        if limit.closest_distance < limit.rigid {
+            findings.push(Finding {
+                severity: Severity::Unstable,
+                message: format!(
+                    "{} comes within {:e} m of {}, inside its Roche limit of {:e} m",
+                    limit.body, limit.closest_distance, limit.primary, limit.rigid
+                ),
+            });
+        } else if limit.closest_distance < limit.fluid {
+            findings.push(Finding {
+                severity: Severity::Marginal,
+                message: format!(
+                    "{} comes within {:e} m of {}, inside the Roche limit of a fluid body of {:e} m",
+                    limit.body, limit.closest_distance, limit.primary, limit.fluid
+                ),
+            });
+        }
+        roche_limits.push(limit);
+    }
+
+    StabilityReport {
+        dominant_body: dominant.map(|dominant| bodies[dominant].name.clone()),
+        hill_separations,
+        roche_limits,
+        findings,
+    }
+}
+
+impl StabilityReport {
+    /// Whether a check found the system obviously unstable
+    pub fn is_unstable(&self) -> bool {
+        self.findings
+            .iter()
+            .any(|finding| finding.severity == Severity::Unstable)
+    }
+
+    /// Logs the findings, the obviously unstable ones as warnings
+    pub fn log(&self) {
+        for finding in self.findings.iter() {
+            match finding.severity {
+                Severity::Unstable => {
+                    tracing::event!(tracing::Level::WARN, "{}", finding.message)
+                }
+                Severity::Marginal => {
+                    tracing::event!(tracing::Level::INFO, "{}", finding.message)
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for StabilityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.dominant_body {
+            Some(dominant) => writeln!(f, "Dominant body  {dominant}")?,
+            None => writeln!(
+                f,
+                "No body dominates the mass of the system, Hill stability not checked"
+            )?,
+        }
+        if !self.hill_separations.is_empty() {
+            writeln!(f, "Separations in mutual Hill radii")?;
+            for separation in self.hill_separations.iter() {
+                writeln!(
+                    f,
+                    "  {} – {}: {:.2} ({:e} m)",
+                    separation.inner,
+                    separation.outer,
+                    separation.mutual_hill_radii,
+                    separation.distance
+                )?;
+            }
+        }
+        if !self.roche_limits.is_empty() {
+            writeln!(f, "Closest distance / Roche limit (rigid, fluid)")?;
+            for limit in self.roche_limits.iter() {
+                writeln!(
+                    f,
+                    "  {} about {}: {:.2}, {:.2}",
+                    limit.body,
+                    limit.primary,
+                    limit.closest_distance / limit.rigid,
+                    limit.closest_distance / limit.fluid
+                )?;
+            }
+        }
+        if self.findings.is_empty() {
+            writeln!(f, "No obvious instabilities found")?;
+        }
+        for finding in self.findings.iter() {
+            let label = match finding.severity {
+                Severity::Unstable => "Unstable",
+                Severity::Marginal => "Marginal",
+            };
+            writeln!(f, "{label}: {}", finding.message)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{check_stability, Severity};
+    use crate::{
+        configsystem::BodyProperties,
+        generate::{circular_speed, ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS},
+    };
+    use kepler_core::types::{Body, System};
+    use maths_rs::Vec2d;
+
+    fn orbiting(name: &str, mass: f64, primary: &Body, distance: f64) -> Body {
+        Body {
+            name: name.to_owned(),
+            mass,
+            position: primary.position + Vec2d::new(distance, 0.0),
+            velocity: primary.velocity + Vec2d::new(0.0, circular_speed(primary.mass, distance)),
+        }
+    }
+
+    #[test]
+    pub fn finds_close_planets_and_moons_inside_the_roche_limit() {
+        let sun = Body {
+            name: "Sun".to_owned(),
+            mass: SUN_MASS,
+            position: Vec2d::new(0.0, 0.0),
+            velocity: Vec2d::new(0.0, 0.0),
+        };
+        let earth = orbiting("Earth", EARTH_MASS, &sun, ASTRONOMICAL_UNIT);
+        let moon = orbiting("Moon", 7.342e22, &earth, 3.844e8);
+        let twin = orbiting("Twin", EARTH_MASS, &sun, 1.01 * ASTRONOMICAL_UNIT);
+        let mut body_properties = BTreeMap::from([(
+            "Moon".to_owned(),
+            BodyProperties {
+                radius: Some(1.737e6),
+                density: None,
+            },
+        )]);
+
+        let system = System {
+            bodies: vec![sun.clone(), earth.clone(), moon.clone()],
+        };
+        let report = check_stability(&system, &body_properties);
+        assert_eq!(report.dominant_body.as_deref(), Some("Sun"));
+        // the moon is a satellite of the earth, not a planet next to it
+        assert!(report.hill_separations.is_empty());
+        assert_eq!(report.roche_limits[0].primary, "Earth");
+        assert!(report.findings.is_empty(), "{report}");
+
+        let system = System {
+            bodies: vec![
+                sun,
+                earth.clone(),
+                twin,
+                orbiting("Moon", 7.342e22, &earth, 5e6),
+            ],
+        };
+        body_properties.insert(
+            "Twin".to_owned(),
+            BodyProperties {
+                radius: None,
+                density: Some(5500.0),
+            },
+        );
+        let report = check_stability(&system, &body_properties);
+        assert!(report.is_unstable());
+        assert_eq!(report.hill_separations.len(), 1);
+        assert!(report.hill_separations[0].mutual_hill_radii < 3.0);
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .filter(|finding| finding.severity == Severity::Unstable)
+                .count(),
+            2,
+            "{report}"
+        );
+    }
+}
@@ -0,0 +1,539 @@
+use std::time::Instant;
+
+use kepler_core::{
+    center_of_mass::calculate_center_of_mass, impulse::calculate_total_impulse, types::System,
+};
+use maths_rs::{num::Cast, Vec2d};
+use serde::Serialize;
+
+use crate::{
+    chaos::ChaosIndicators, diagnostics::calculate_total_energy, gravity::GRAVITATIONAL_CONSTANT,
+    simulation::format_signed_time,
+};
+
+/// The smallest distance between two bodies during a run
+#[derive(Debug, Clone, Serialize)]
+pub struct ClosestApproach {
+    pub body_a: String,
+    pub body_b: String,
+    pub distance: f64,
+    pub time: f64,
+}
+
+/// Key figures of a finished run, written to `<prefix>_summary.json`
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub steps: i64,
+    pub simulated_time: f64,
+    pub wall_clock_seconds: f64,
+    pub steps_per_second: f64,
+    pub initial_energy: f64,
+    pub final_energy: f64,
+    /// Largest |E - E₀| / |E₀| over all export steps
+    pub max_relative_energy_error: f64,
+    /// Length of the change of the total impulse
+    pub momentum_drift: f64,
+    /// Distance the center of mass moved
+    pub center_of_mass_drift: f64,
+    pub closest_approach: Option<ClosestApproach>,
+    /// The pairs of bodies that came closest to each other, closest first
+    pub closest_encounters: Vec<ClosestApproach>,
+    /// Number of steps at which data was exported or collected for plotting
+    pub export_steps: i64,
+    /// At the end of the run, if `export_chaos_indicators` is set
+    pub chaos_indicators: Option<ChaosIndicators>,
+    pub events: EventCounts,
+}
+
+/// How often something noteworthy happened during a run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EventCounts {
+    /// Approaches of two bodies closer than `close_encounter_distance`, each
+    /// approach counted once, `None` if no distance is configured
+    pub close_encounters: Option<u64>,
+    /// Bodies that became unbound from the rest of the system and move away
+    /// from it, sampled at the export steps
+    pub escapes: u64,
+    /// Bodies whose position or velocity became NaN or infinite
+    pub non_finite_states: u64,
+}
+
+/// Counts the events of a run, every body escapes or diverges at most once
+struct EventCounter {
+    counts: EventCounts,
+    close_encounter_distance: Option<f64>,
+    /// Whether the pairs `i < j` are within the close encounter distance, row by row
+    encountering: Vec<bool>,
+    escaped: Vec<bool>,
+    non_finite: Vec<bool>,
+}
+
+impl EventCounter {
+    fn new(system: &System, close_encounter_distance: Option<f64>) -> Self {
+        let count = system.bodies.len();
+        Self {
+            counts: EventCounts {
+                close_encounters: close_encounter_distance.map(|_| 0),
+                ..EventCounts::default()
+            },
+            close_encounter_distance,
+            encountering: vec![false; count * count.saturating_sub(1) / 2],
+            escaped: vec![false; count],
+            non_finite: vec![false; count],
+        }
+    }
+
+    fn track_step(&mut self, system: &System) {
+        for (body, non_finite) in system.bodies.iter().zip(self.non_finite.iter_mut()) {
+            let finite = [body.position, body.velocity]
+                .iter()
+                .all(|vector| vector.x.is_finite() && vector.y.is_finite());
+            if !finite && !*non_finite {
+                *non_finite = true;
+                self.counts.non_finite_states += 1;
+            }
+        }
+
+        let (Some(threshold), Some(encounters)) = (
+            self.close_encounter_distance,
+            self.counts.close_encounters.as_mut(),
+        ) else {
+            return;
+        };
+        let mut encountering = self.encountering.iter_mut();
+        for (i, body) in system.bodies.iter().enumerate() {
+            for other in system.bodies.iter().skip(i + 1) {
+                let Some(encountering) = encountering.next() else {
+                    return;
+                };
+                let close = length(other.position - body.position) < threshold;
+                if close && !*encountering {
+                    *encounters += 1;
+                }
+                *encountering = close;
+            }
+        }
+    }
+
+    /// A body escapes when it is unbound from all other bodies and moves away from
+    /// the center of mass of the system
+    fn track_export(&mut self, system: &System) {
+        let total_mass: f64 = system.bodies.iter().map(|body| body.mass).sum();
+        if total_mass <= 0.0 {
+            return;
+        }
+        let center_of_mass = calculate_center_of_mass(system);
+        let drift = calculate_total_impulse(system) * (1.0 / total_mass);
+        for (i, body) in system.bodies.iter().enumerate() {
+            if self.escaped[i] {
+                continue;
+            }
+            let position = body.position - center_of_mass;
+            let velocity = body.velocity - drift;
+            let potential: f64 = system
+                .bodies
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other)| {
+                    -GRAVITATIONAL_CONSTANT * other.mass / length(other.position - body.position)
+                })
+                .sum();
+            let energy = (velocity.x * velocity.x + velocity.y * velocity.y) / 2.0 + potential;
+            let receding = position.x * velocity.x + position.y * velocity.y > 0.0;
+            if energy > 0.0 && receding {
+                self.escaped[i] = true;
+                self.counts.escapes += 1;
+            }
+        }
+    }
+}
+
+/// Collects the data for the `RunSummary` while the simulation runs
+pub struct SummaryTracker {
+    start: Instant,
+    initial_energy: f64,
+    initial_impulse: Vec2d,
+    initial_center_of_mass: Vec2d,
+    max_relative_energy_error: f64,
+    min_distances: MinDistances,
+    events: EventCounter,
+    export_steps: i64,
+}
+
+/// The number of pairs listed in `RunSummary::closest_encounters`
+const CLOSEST_ENCOUNTERS: usize = 10;
+
+/// The smallest distance every pair of bodies reached during a run, and when
+pub struct MinDistances {
+    pub names: Vec<String>,
+    /// Distance and time for every pair `i < j`, row by row
+    pairs: Vec<(f64, f64)>,
+}
+
+impl MinDistances {
+    pub fn new(system: &System) -> Self {
+        let count = system.bodies.len();
+        Self {
+            names: system.bodies.iter().map(|body| body.name.clone()).collect(),
+            pairs: vec![(f64::INFINITY, 0.0); count * count.saturating_sub(1) / 2],
+        }
+    }
+
+    fn index(&self, i: usize, j: usize) -> usize {
+        let (i, j) = (i.min(j), i.max(j));
+        i * (2 * self.names.len() - i - 1) / 2 + (j - i - 1)
+    }
+
+    /// The smallest distance of bodies `i` and `j` and the time it was reached,
+    /// `None` for `i == j`
+    pub fn get(&self, i: usize, j: usize) -> Option<(f64, f64)> {
+        (i != j).then(|| self.pairs[self.index(i, j)])
+    }
+
+    /// Updates the smallest distances with the current positions
+    pub fn track(&mut self, system: &System, time: f64) {
+        let mut pairs = self.pairs.iter_mut();
+        for (i, body) in system.bodies.iter().enumerate() {
+            for other in system.bodies.iter().skip(i + 1) {
+                let Some(pair) = pairs.next() else {
+                    return;
+                };
+                let distance = length(other.position - body.position);
+                if distance < pair.0 {
+                    *pair = (distance, time);
+                }
+            }
+        }
+    }
+
+    /// All pairs, closest first
+    pub fn ranked(&self) -> Vec<ClosestApproach> {
+        let count = self.names.len();
+        let mut ranked: Vec<ClosestApproach> = (0..count)
+            .flat_map(|i| (i + 1..count).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let (distance, time) = self.pairs[self.index(i, j)];
+                ClosestApproach {
+                    body_a: self.names[i].clone(),
+                    body_b: self.names[j].clone(),
+                    distance,
+                    time,
+                }
+            })
+            .collect();
+        ranked.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        ranked
+    }
+}
+
+fn length(vector: Vec2d) -> f64 {
+    (vector.x * vector.x + vector.y * vector.y).sqrt()
+}
+
+/// Returns the indices and the distance of the two bodies closest to each other
+pub fn closest_pair(system: &System) -> Option<(usize, usize, f64)> {
+    let mut closest: Option<(usize, usize, f64)> = None;
+    for (i, body) in system.bodies.iter().enumerate() {
+        for (j, other) in system.bodies.iter().enumerate().skip(i + 1) {
+            let distance = length(other.position - body.position);
+            if closest.is_none_or(|(_, _, min)| distance < min) {
+                closest = Some((i, j, distance));
+            }
+        }
+    }
+    closest
+}
+
+impl SummaryTracker {
+    pub fn new(system: &System, close_encounter_distance: Option<f64>) -> Self {
+        let mut tracker = Self {
+            start: Instant::now(),
+            initial_energy: calculate_total_energy(system),
+            initial_impulse: calculate_total_impulse(system),
+            initial_center_of_mass: calculate_center_of_mass(system),
+            max_relative_energy_error: 0.0,
+            min_distances: MinDistances::new(system),
+            events: EventCounter::new(system, close_encounter_distance),
+            export_steps: 0,
+        };
+        tracker.track_step(system, 0.0);
+        tracker
+    }
+
+    fn relative_energy_error(&self, energy: f64) -> f64 {
+        if self.initial_energy == 0.0 {
+            (energy - self.initial_energy).abs()
+        } else {
+            ((energy - self.initial_energy) / self.initial_energy).abs()
+        }
+    }
+
+    /// Called after every step to track the closest approach of every pair of bodies
+    /// and count the close encounters
+    pub fn track_step(&mut self, system: &System, time: f64) {
+        self.min_distances.track(system, time);
+        self.events.track_step(system);
+    }
+
+    pub fn min_distances(&self) -> &MinDistances {
+        &self.min_distances
+    }
+
+    /// Called at every export step, the energy error is only sampled here
+    pub fn track_export(&mut self, system: &System) {
+        self.export_steps += 1;
+        self.events.track_export(system);
+        let error = self.relative_energy_error(calculate_total_energy(system));
+        self.max_relative_energy_error = self.max_relative_energy_error.max(error);
+    }
+
+    pub fn finish(
+        self,
+        system: &System,
+        steps: i64,
+        time: f64,
+        chaos_indicators: Option<ChaosIndicators>,
+    ) -> RunSummary {
+        let wall_clock_seconds = self.start.elapsed().as_secs_f64();
+        let final_energy = calculate_total_energy(system);
+        let mut closest_encounters = self.min_distances.ranked();
+        closest_encounters.truncate(CLOSEST_ENCOUNTERS);
+        RunSummary {
+            steps,
+            simulated_time: time,
+            wall_clock_seconds,
+            steps_per_second: steps.as_f64() / wall_clock_seconds,
+            initial_energy: self.initial_energy,
+            final_energy,
+            max_relative_energy_error: self
+                .max_relative_energy_error
+                .max(self.relative_energy_error(final_energy)),
+            momentum_drift: length(calculate_total_impulse(system) - self.initial_impulse),
+            center_of_mass_drift: length(
+                calculate_center_of_mass(system) - self.initial_center_of_mass,
+            ),
+            closest_approach: closest_encounters.first().cloned(),
+            closest_encounters,
+            export_steps: self.export_steps,
+            chaos_indicators,
+            events: self.events.counts,
+        }
+    }
+}
+
+impl std::fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut rows = vec![
+            ("Steps", self.steps.to_string()),
+            (
+                "Simulated time",
+                format!(
+                    "{} ({:e} s)",
+                    format_signed_time(self.simulated_time),
+                    self.simulated_time
+                ),
+            ),
+            (
+                "Wall-clock time",
+                format!("{:.3} s", self.wall_clock_seconds),
+            ),
+            ("Steps per second", format!("{:.1}", self.steps_per_second)),
+            ("Initial energy", format!("{:e} J", self.initial_energy)),
+            ("Final energy", format!("{:e} J", self.final_energy)),
+            (
+                "Max. relative energy error",
+                format!("{:e}", self.max_relative_energy_error),
+            ),
+            (
+                "Momentum drift",
+                format!("{:e} kg m/s", self.momentum_drift),
+            ),
+            (
+                "Center of mass drift",
+                format!("{:e} m", self.center_of_mass_drift),
+            ),
+            (
+                "Closest approach",
+                match &self.closest_approach {
+                    Some(closest) => format!(
+                        "{:e} m between {} and {} at {}",
+                        closest.distance,
+                        closest.body_a,
+                        closest.body_b,
+                        format_signed_time(closest.time)
+                    ),
+                    None => "-".to_owned(),
+                },
+            ),
+            ("Export steps", self.export_steps.to_string()),
+            (
+                "Close encounters",
+                match self.events.close_encounters {
+                    Some(count) => count.to_string(),
+                    None => "-".to_owned(),
+                },
+            ),
+            ("Escapes", self.events.escapes.to_string()),
+            (
+                "Non-finite states",
+                self.events.non_finite_states.to_string(),
+            ),
+        ];
+        if let Some(chaos) = &self.chaos_indicators {
+            rows.push((
+                "Lyapunov exponent",
+                format!("{:e} 1/s", chaos.lyapunov_exponent),
+            ));
+            rows.push(("MEGNO", format!("{:.3}", chaos.megno)));
+        }
+        let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        for (label, value) in rows {
+            writeln!(f, "{label:<width$}  {value}")?;
+        }
+        if self.closest_encounters.len() > 1 {
+            writeln!(f, "Closest encounters")?;
+            for (rank, encounter) in self.closest_encounters.iter().enumerate() {
+                writeln!(
+                    f,
+                    "{:>4}. {} – {}: {:e} m at {}",
+                    rank + 1,
+                    encounter.body_a,
+                    encounter.body_b,
+                    encounter.distance,
+                    format_signed_time(encounter.time)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{closest_pair, MinDistances, SummaryTracker};
+    use crate::generate::{ASTRONOMICAL_UNIT, EARTH_MASS, SUN_MASS};
+    use kepler_core::{
+        mover::system_timestep,
+        types::{Body, System},
+    };
+    use maths_rs::Vec2d;
+
+    #[test]
+    pub fn finds_the_closest_pair() {
+        let body = |name: &str, x: f64, y: f64| Body {
+            name: name.to_owned(),
+            mass: 1.0,
+            position: Vec2d::new(x, y),
+            velocity: Vec2d::new(0.0, 0.0),
+        };
+        let system = System {
+            bodies: vec![
+                body("A", 0.0, 0.0),
+                body("B", 10.0, 0.0),
+                body("C", 10.0, 3.0),
+            ],
+        };
+        assert_eq!(closest_pair(&system), Some((1, 2, 3.0)));
+    }
+
+    #[test]
+    pub fn tracks_the_min_distance_of_every_pair() {
+        let body = |name: &str, x: f64| Body {
+            name: name.to_owned(),
+            mass: 1.0,
+            position: Vec2d::new(x, 0.0),
+            velocity: Vec2d::new(0.0, 0.0),
+        };
+        let mut min_distances = MinDistances::new(&System {
+            bodies: vec![body("A", 0.0), body("B", 10.0), body("C", 30.0)],
+        });
+        min_distances.track(
+            &System {
+                bodies: vec![body("A", 0.0), body("B", 10.0), body("C", 30.0)],
+            },
+            0.0,
+        );
+        min_distances.track(
+            &System {
+                bodies: vec![body("A", 0.0), body("B", 20.0), body("C", 25.0)],
+            },
+            5.0,
+        );
+
+        assert_eq!(min_distances.get(0, 1), Some((10.0, 0.0)));
+        assert_eq!(min_distances.get(2, 1), Some((5.0, 5.0)));
+        assert_eq!(min_distances.get(0, 2), Some((25.0, 5.0)));
+        assert_eq!(min_distances.get(1, 1), None);
+        let ranked: Vec<_> = min_distances
+            .ranked()
+            .into_iter()
+            .map(|encounter| (encounter.body_a, encounter.body_b))
+            .collect();
+        assert_eq!(
+            ranked,
+            [("B", "C"), ("A", "B"), ("A", "C")].map(|(a, b)| (a.to_owned(), b.to_owned()))
+        );
+    }
+
+    #[test]
+    pub fn counts_close_encounters_and_escapes() {
+        let body = |name: &str, x: f64, vx: f64| Body {
+            name: name.to_owned(),
+            mass: 1e20,
+            position: Vec2d::new(x, 0.0),
+            velocity: Vec2d::new(vx, 0.0),
+        };
+        let system = |b: f64, c: f64| System {
+            bodies: vec![body("A", 0.0, 0.0), body("B", b, 0.0), body("C", c, 1e3)],
+        };
+        let mut tracker = SummaryTracker::new(&system(100.0, 1e9), Some(10.0));
+        // B approaches A twice, C flies off much faster than the escape speed
+        for (time, b) in [5.0, 50.0, 5.0, 4.0].into_iter().enumerate() {
+            let system = system(b, 1e9 + time as f64 * 1e3);
+            tracker.track_step(&system, time as f64);
+            tracker.track_export(&system);
+        }
+        let summary = tracker.finish(&system(4.0, 1e9), 4, 4.0, None);
+        assert_eq!(summary.events.close_encounters, Some(2));
+        assert_eq!(summary.events.escapes, 1);
+        assert_eq!(summary.events.non_finite_states, 0);
+    }
+
+    #[test]
+    pub fn energy_error_shrinks_with_the_timestep() {
+        // an eccentric orbit that starts at aphelion and passes perihelion after half a year
+        let system = System {
+            bodies: vec![
+                Body {
+                    name: "Sun".to_owned(),
+                    mass: SUN_MASS,
+                    position: Vec2d::new(0.0, 0.0),
+                    velocity: Vec2d::new(0.0, 0.0),
+                },
+                Body {
+                    name: "Earth".to_owned(),
+                    mass: EARTH_MASS,
+                    position: Vec2d::new(ASTRONOMICAL_UNIT, 0.0),
+                    velocity: Vec2d::new(0.0, 24000.0),
+                },
+            ],
+        };
+        let energy_error = |timestep: f64| {
+            let steps = (200.0 * 86400.0 / timestep) as i64;
+            let mut system = system.clone();
+            let mut tracker = SummaryTracker::new(&system, None);
+            for step in 1..=steps {
+                system = system_timestep(system, timestep);
+                tracker.track_export(&system);
+                tracker.track_step(&system, step as f64 * timestep);
+            }
+            tracker
+                .finish(&system, steps, steps as f64 * timestep, None)
+                .max_relative_energy_error
+        };
+        let (coarse, fine) = (energy_error(600.0), energy_error(60.0));
+        assert!(coarse < 1e-2, "{coarse}");
+        assert!(fine < coarse / 5.0, "{fine} {coarse}");
+    }
+}
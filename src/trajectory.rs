@@ -0,0 +1,128 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::error::ApplicationError;
+
+/// The state of a body at one exported step
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct State {
+    #[serde(rename = "Step")]
+    pub step: i64,
+    #[serde(rename = "Time")]
+    pub time: f64,
+    #[serde(rename = "Mass")]
+    pub mass: f64,
+    pub x: f64,
+    pub y: f64,
+    pub vx: f64,
+    pub vy: f64,
+}
+
+/// One line of a `--stdout jsonl` stream, only the states of the bodies
+#[derive(Deserialize)]
+struct StateRecord {
+    step: i64,
+    time: f64,
+    bodies: Vec<BodyState>,
+}
+
+#[derive(Deserialize)]
+struct BodyState {
+    name: String,
+    mass: f64,
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+}
+
+/// The exported states of every body by name and step
+pub type Trajectories = BTreeMap<String, BTreeMap<i64, State>>;
+
+fn read_error(path: &Path, message: impl std::fmt::Display) -> ApplicationError {
+    ApplicationError::TrajectoryError(format!("{}: {message}", path.display()))
+}
+
+/// Reads the body histories `<prefix>_<body>.csv` of an export directory
+fn read_directory(directory: &Path, prefix: &str) -> Result<Trajectories, ApplicationError> {
+    const HEADERS: [&str; 7] = ["Step", "Time", "Mass", "x", "y", "vx", "vy"];
+
+    let mut trajectories = Trajectories::new();
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+    for path in paths {
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(prefix)?.strip_prefix('_'))
+            .and_then(|name| name.strip_suffix(".csv"))
+        else {
+            continue;
+        };
+        let mut reader = csv::Reader::from_path(&path)?;
+        // the snapshots, system parameters and test particles share the prefix
+        if reader.headers()?.iter().ne(HEADERS) {
+            continue;
+        }
+        let states = reader
+            .deserialize()
+            .map(|state| state.map(|state: State| (state.step, state)))
+            .collect::<Result<_, _>>()
+            .map_err(|e| read_error(&path, e))?;
+        trajectories.insert(name.to_owned(), states);
+    }
+    if trajectories.is_empty() {
+        return Err(read_error(
+            directory,
+            format_args!("there are no body histories `{prefix}_<body>.csv`, check --prefix and `export_body_history`"),
+        ));
+    }
+    Ok(trajectories)
+}
+
+/// Reads a stream written with `--stdout jsonl`
+fn read_jsonl(file: &Path) -> Result<Trajectories, ApplicationError> {
+    let mut trajectories = Trajectories::new();
+    for (index, line) in std::fs::read_to_string(file)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: StateRecord = serde_json::from_str(line)
+            .map_err(|e| read_error(file, format_args!("line {}: {e}", index + 1)))?;
+        for body in record.bodies {
+            trajectories.entry(body.name).or_default().insert(
+                record.step,
+                State {
+                    step: record.step,
+                    time: record.time,
+                    mass: body.mass,
+                    x: body.x,
+                    y: body.y,
+                    vx: body.vx,
+                    vy: body.vy,
+                },
+            );
+        }
+    }
+    Ok(trajectories)
+}
+
+/// Reads the trajectories of the bodies of a run, from the body histories in an
+/// export directory or from a `.jsonl` stream
+pub fn read_trajectories(
+    path: impl AsRef<Path>,
+    prefix: &str,
+) -> Result<Trajectories, ApplicationError> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        read_directory(path, prefix)
+    } else {
+        read_jsonl(path)
+    }
+}